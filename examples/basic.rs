@@ -1,6 +1,6 @@
 #![allow(dead_code)]
 use tracing::subscriber::set_global_default;
-use tracing_sprout::TrunkLayer;
+use tracing_sprout::{ProcessFields, TrunkLayer};
 use tracing_subscriber::prelude::*;
 use tracing_subscriber::{EnvFilter, Registry};
 
@@ -25,7 +25,8 @@ fn main() {
         "I'm Groot".to_string(),
         env!("CARGO_PKG_VERSION").to_string(),
         std::io::stdout,
-    );
+    )
+    .with_process_fields(ProcessFields::PID);
     let subscriber = Registry::default().with(env_filter).with(formatting_layer);
 
     set_global_default(subscriber).expect("failed to set up global tracing subscriber");
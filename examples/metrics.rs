@@ -0,0 +1,42 @@
+use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+use tracing::subscriber::set_global_default;
+use tracing::{info, info_span, warn};
+use tracing_sprout::{SpanEvents, TrunkLayer};
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::Registry;
+
+fn main() {
+    // In a real application this would be something like a Prometheus recorder
+    let recorder = DebuggingRecorder::new();
+    let snapshotter = recorder.snapshotter();
+    recorder
+        .install()
+        .expect("failed to install the metrics recorder");
+
+    let formatting_layer = TrunkLayer::new(
+        "I'm Groot".to_string(),
+        env!("CARGO_PKG_VERSION").to_string(),
+        std::io::stdout,
+    )
+    .with_span_events(SpanEvents::NONE)
+    .with_metrics(["route"]);
+    let subscriber = Registry::default().with(formatting_layer);
+
+    set_global_default(subscriber).expect("failed to set up global tracing subscriber");
+
+    for _ in 0..3 {
+        let span = info_span!("http_request", route = "/groot");
+        let _guard = span.enter();
+        info!("I am Groot");
+    }
+    warn!("We are Groot");
+
+    for (key, _, _, value) in snapshotter.snapshot().into_vec() {
+        let value = match value {
+            DebugValue::Counter(count) => count.to_string(),
+            DebugValue::Gauge(gauge) => gauge.to_string(),
+            DebugValue::Histogram(samples) => format!("{} samples", samples.len()),
+        };
+        println!("{:?} => {}", key.key(), value);
+    }
+}
@@ -0,0 +1,39 @@
+use opentelemetry::trace::{TraceContextExt, TracerProvider as _};
+use opentelemetry_sdk::trace::TracerProvider;
+use tracing::subscriber::set_global_default;
+use tracing::{info, info_span};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_sprout::TrunkLayer;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::Registry;
+
+fn main() {
+    // In a real application this would be configured with an exporter
+    let provider = TracerProvider::builder().build();
+    let tracer = provider.tracer("I'm Groot");
+
+    let formatting_layer = TrunkLayer::new(
+        "I'm Groot".to_string(),
+        env!("CARGO_PKG_VERSION").to_string(),
+        std::io::stdout,
+    );
+
+    // The OpenTelemetry layer needs to see each span before sprout does
+    let subscriber = Registry::default()
+        .with(tracing_opentelemetry::layer().with_tracer(tracer))
+        .with(formatting_layer);
+
+    set_global_default(subscriber).expect("failed to set up global tracing subscriber");
+
+    let span = info_span!("Guardians of the galaxy");
+    let _guard = span.enter();
+
+    // These should match the `trace_id` and `span_id` on the records
+    let context = span.context();
+    let span_context = context.span().span_context().clone();
+    info!(
+        otel_trace_id = %span_context.trace_id(),
+        otel_span_id = %span_context.span_id(),
+        "We are Groot"
+    );
+}
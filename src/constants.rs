@@ -1,12 +1,93 @@
 // Metadata we're enhancing the spans with
 pub(crate) const TIME: &str = "time";
 pub(crate) const MESSAGE: &str = "msg";
-pub(crate) const ELAPSED_MILLIS: &str = "elapsed_time_ms";
-pub(crate) const TIME_SINCE_START: &str = "time_since_span_entered_ms";
+
+// Describing the process
+pub(crate) const PID: &str = "pid";
+pub(crate) const HOSTNAME: &str = "hostname";
+pub(crate) const EXECUTABLE: &str = "executable";
+pub(crate) const OS_THREAD_ID: &str = "os_thread_id";
+
+// Describing the container & the Kubernetes pod the process is running in
+pub(crate) const CONTAINER_ID: &str = "container_id";
+pub(crate) const POD_NAME: &str = "pod_name";
+pub(crate) const POD_NAMESPACE: &str = "pod_namespace";
+pub(crate) const NODE_NAME: &str = "node_name";
+pub(crate) const CPU_LIMIT: &str = "cpu_limit";
+pub(crate) const MEMORY_LIMIT: &str = "memory_limit_bytes";
+
+// Durations, the unit is appended to the key when it's written
+pub(crate) const ELAPSED: &str = "elapsed_time";
+pub(crate) const TIME_SINCE_START: &str = "time_since_span_entered";
+pub(crate) const TIME_SINCE_CREATED: &str = "time_since_span_started";
+pub(crate) const TIME_SINCE_ROOT: &str = "time_since_root_span_started";
+pub(crate) const BUSY: &str = "busy_time";
+pub(crate) const IDLE: &str = "idle_time";
+
+pub(crate) const ENTER_COUNT: &str = "enter_count";
+
+// Span statistics
+pub(crate) const STATS_SPAN: &str = "span";
+pub(crate) const STATS_COUNT: &str = "count";
+pub(crate) const STATS_MIN: &str = "min";
+pub(crate) const STATS_MAX: &str = "max";
+pub(crate) const STATS_MEAN: &str = "mean";
+pub(crate) const STATS_P50: &str = "p50";
+pub(crate) const STATS_P90: &str = "p90";
+pub(crate) const STATS_P99: &str = "p99";
+
+// Events that happened within a span
+pub(crate) const EVENT_COUNTS: &str = "event_counts";
+pub(crate) const FIRST_ERROR: &str = "first_error";
+
+// Failed spans
+pub(crate) const ERROR: &str = "error";
+pub(crate) const STATUS: &str = "status";
+pub(crate) const STATUS_ERROR: &str = "error";
+
+// Values recorded on a span after it was created
+pub(crate) const CHANGES: &str = "changes";
+pub(crate) const BEFORE: &str = "before";
+pub(crate) const AFTER: &str = "after";
+
+// Inherited values kept alongside the values that collided with them
+pub(crate) const PARENT_PREFIX: &str = "parent.";
+
+// Whole trees written as one document
+pub(crate) const TREE_EVENTS: &str = "events";
+pub(crate) const TREE_CHILDREN: &str = "children";
+
+// Slow spans
+pub(crate) const SLOW: &str = "slow";
+pub(crate) const SLOW_THRESHOLD: &str = "slow_threshold";
+
+// Identifiers of the span the record belongs to
+pub(crate) const SPAN_ID_KEY: &str = "span_id";
+pub(crate) const PARENT_SPAN_ID: &str = "parent_span_id";
+pub(crate) const ROOT_SPAN_ID: &str = "root_span_id";
+
+// W3C trace context
+pub(crate) const TRACE_ID: &str = "trace_id";
+pub(crate) const TRACE_FLAGS: &str = "trace_flags";
+pub(crate) const TRACEPARENT: &str = "traceparent";
+pub(crate) const TRACESTATE: &str = "tracestate";
+
+/// The ancestry of the record, from the root span down
+pub(crate) const SPANS: &str = "spans";
+pub(crate) const SPAN_PATH: &str = "span_path";
+pub(crate) const SPAN_PATH_SEPARATOR: &str = "::";
+
+// Keys used for each entry in the `spans` array
+pub(crate) const SPAN_LIST_NAME: &str = "name";
+pub(crate) const SPAN_LIST_ID: &str = "id";
+pub(crate) const SPAN_LIST_FIELDS: &str = "fields";
 
 /// Type of the span
 pub(crate) const TYPE: &str = "span_type";
 
+/// The target used for records that sprout writes on its own behalf
+pub(crate) const SPROUT_TARGET: &str = "sprout";
+
 // Span Metadata
 pub(crate) const LEVEL: &str = "level";
 pub(crate) const TARGET: &str = "target";
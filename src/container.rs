@@ -0,0 +1,175 @@
+use json::JsonValue;
+
+use std::env;
+use std::fs;
+
+use crate::constants::{CONTAINER_ID, CPU_LIMIT, MEMORY_LIMIT, NODE_NAME, POD_NAME, POD_NAMESPACE};
+use crate::util::insert_lossy;
+
+/// The environment variables conventionally set through the Kubernetes downward API
+const DOWNWARD_API: [(&str, &str); 3] = [
+    ("POD_NAME", POD_NAME),
+    ("POD_NAMESPACE", POD_NAMESPACE),
+    ("NODE_NAME", NODE_NAME),
+];
+
+/// cgroup v1 reports an unlimited amount of memory as a number close to `i64::MAX`
+const UNLIMITED_MEMORY: u64 = 1 << 62;
+
+/// Adds whatever can be found out about the container the process is running in, anything that
+/// can't be found _(i.e. because it isn't running in a container)_ is left out
+pub(crate) fn insert_container(attributes: &mut JsonValue) {
+    if let Some(container_id) = container_id() {
+        insert_lossy(attributes, CONTAINER_ID, container_id);
+    }
+    for (var, key) in DOWNWARD_API.iter() {
+        if let Some(value) = env::var(var).ok().filter(|value| !value.is_empty()) {
+            insert_lossy(attributes, key, value);
+        }
+    }
+    if let Some(cpu_limit) = cpu_limit() {
+        insert_lossy(attributes, CPU_LIMIT, cpu_limit);
+    }
+    if let Some(memory_limit) = memory_limit() {
+        insert_lossy(attributes, MEMORY_LIMIT, memory_limit);
+    }
+}
+
+/// Container runtimes name the cgroup _(or the mounts)_ of the container after its id, which is
+/// 64 hex characters, i.e. `/docker/<id>` or `cri-containerd-<id>.scope`.
+///
+/// cgroup v2 usually hides the path from the container, but the runtime still mounts files such
+/// as `/etc/hostname` from a `/containers/<id>/` directory.
+fn container_id() -> Option<String> {
+    let from_cgroup = fs::read_to_string("/proc/self/cgroup")
+        .ok()
+        .and_then(|contents| container_id_from_cgroup(&contents));
+    from_cgroup.or_else(|| {
+        fs::read_to_string("/proc/self/mountinfo")
+            .ok()
+            .and_then(|contents| container_id_from_mountinfo(&contents))
+    })
+}
+
+/// Each line of `/proc/self/cgroup` is `<hierarchy>:<controllers>:<path>`
+fn container_id_from_cgroup(contents: &str) -> Option<String> {
+    contents
+        .lines()
+        .filter_map(|line| line.splitn(3, ':').nth(2))
+        .find_map(|path| {
+            let segments: Vec<_> = path.split('/').collect();
+            segments.iter().enumerate().find_map(|(i, segment)| {
+                let id = match segments.get(i + 1) {
+                    Some(next) if *segment == "docker" => Some(*next),
+                    _ => segment
+                        .strip_prefix("cri-containerd-")
+                        .and_then(|rest| rest.strip_suffix(".scope")),
+                };
+                id.filter(|id| is_container_id(id)).map(String::from)
+            })
+        })
+}
+
+fn container_id_from_mountinfo(contents: &str) -> Option<String> {
+    contents
+        .split_whitespace()
+        .flat_map(|path| path.split("/containers/").skip(1))
+        .find_map(|rest| {
+            let (id, _) = rest.split_once('/')?;
+            Some(id).filter(|id| is_container_id(id)).map(String::from)
+        })
+}
+
+fn is_container_id(id: &str) -> bool {
+    id.len() == 64 && id.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// The number of CPUs the container is limited to, which may well be a fraction
+fn cpu_limit() -> Option<f64> {
+    // cgroup v2 holds `<quota> <period>` in one file, where the quota may be `max`
+    let (quota, period) = match fs::read_to_string("/sys/fs/cgroup/cpu.max") {
+        Ok(contents) => {
+            let mut parts = contents.split_whitespace();
+            (
+                parts.next()?.parse::<f64>().ok()?,
+                parts.next()?.parse().ok()?,
+            )
+        }
+        Err(_) => (
+            read_number("/sys/fs/cgroup/cpu/cpu.cfs_quota_us")?,
+            read_number("/sys/fs/cgroup/cpu/cpu.cfs_period_us")?,
+        ),
+    };
+    // cgroup v1 uses a negative quota for no limit
+    Some(quota / period).filter(|limit| *limit > 0.0 && period > 0.0)
+}
+
+/// The number of bytes of memory the container is limited to
+fn memory_limit() -> Option<u64> {
+    [
+        "/sys/fs/cgroup/memory.max",
+        "/sys/fs/cgroup/memory/memory.limit_in_bytes",
+    ]
+    .iter()
+    // cgroup v2 uses `max` for no limit, which won't parse
+    .find_map(|path| fs::read_to_string(path).ok())
+    .and_then(|contents| contents.trim().parse().ok())
+    .filter(|limit| *limit < UNLIMITED_MEMORY)
+}
+
+fn read_number(path: &str) -> Option<f64> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ID: &str = "3f4b1e4e8d7c0a9b2c6d5e4f3a2b1c0d9e8f7a6b5c4d3e2f1a0b9c8d7e6f5a4b";
+
+    #[test]
+    fn container_id_from_docker_cgroup() {
+        let contents = format!(
+            "12:memory:/docker/{id}\n11:cpu,cpuacct:/docker/{id}\n0::/docker/{id}\n",
+            id = ID
+        );
+        assert_eq!(container_id_from_cgroup(&contents).as_deref(), Some(ID));
+    }
+
+    #[test]
+    fn container_id_from_containerd_cgroup() {
+        let contents = format!(
+            "0::/kubepods.slice/kubepods-burstable.slice/kubepods-burstable-pod1234.slice/cri-containerd-{}.scope\n",
+            ID
+        );
+        assert_eq!(container_id_from_cgroup(&contents).as_deref(), Some(ID));
+    }
+
+    #[test]
+    fn container_id_not_in_cgroup() {
+        // A 64 character hex segment that isn't in a known position isn't a container id
+        let contents = format!("0::/user.slice/{}\n1:name=systemd:/init.scope\n", ID);
+        assert_eq!(container_id_from_cgroup(&contents), None);
+        assert_eq!(container_id_from_cgroup("0::/\n"), None);
+    }
+
+    #[test]
+    fn container_id_from_mounts() {
+        let contents = format!(
+            "1034 1015 0:112 / / rw,relatime master:407 - overlay overlay rw\n\
+             1055 1034 254:1 /var/lib/docker/containers/{id}/hostname /etc/hostname rw,relatime - ext4 /dev/vda1 rw\n",
+            id = ID
+        );
+        assert_eq!(container_id_from_mountinfo(&contents).as_deref(), Some(ID));
+    }
+
+    #[test]
+    fn container_id_not_in_mounts() {
+        let contents = format!(
+            "1055 1034 254:1 /var/lib/images/{}/hostname /etc/hostname rw - ext4 /dev/vda1 rw\n\
+             1056 1034 254:1 /var/lib/docker/containers/not-an-id/hosts /etc/hosts rw - ext4 /dev/vda1 rw\n",
+            ID
+        );
+        assert_eq!(container_id_from_mountinfo(&contents), None);
+    }
+}
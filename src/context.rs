@@ -0,0 +1,258 @@
+use json::JsonValue;
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::env;
+use std::marker::PhantomData;
+
+use crate::propagation::{TraceContext, FLAG_SAMPLED};
+use crate::storage::{with_current_storage, SproutStorage};
+use crate::util::insert_lossy;
+
+/// The environment variable holding the `traceparent` of the parent process
+const TRACEPARENT_ENV: &str = "TRACEPARENT";
+/// The environment variable holding the `tracestate` of the parent process
+const TRACESTATE_ENV: &str = "TRACESTATE";
+/// The environment variable holding the inherited attributes of the parent process, as JSON
+const ATTRIBUTES_ENV: &str = "SPROUT_CONTEXT";
+/// The environment variable holding how many levels each inherited attribute is from the span it
+/// was recorded on, as JSON
+const DEPTHS_ENV: &str = "SPROUT_CONTEXT_DEPTHS";
+
+thread_local! {
+    /// The contexts attached to this thread, the last one is the one in use
+    static ATTACHED: RefCell<Vec<SproutContext>> = const { RefCell::new(Vec::new()) };
+}
+
+/// A snapshot of the inherited attributes & trace ids of a span, that can be re-applied on
+/// another thread or in another process.
+///
+/// Work handed to `std::thread::spawn` or a thread pool doesn't know which span it came from, so
+/// its records would lose their context. Capturing the context before handing the work over and
+/// attaching it on the other side gives any spans created there the same attributes & trace id,
+/// with the span it was captured from as their parent. Events emitted there outside of a span
+/// get the same attributes & ids as if they were emitted within it.
+///
+/// The context is applied one level below the span it was captured from, just like a child span,
+/// so the [`Inheritance`](crate::Inheritance) policies keep applying to the attributes on the
+/// other side. The application's own fields _(such as its name & version)_ are never replaced by
+/// those of the context.
+///
+/// # Examples
+///
+/// ```
+/// use tracing::{info, info_span};
+/// use tracing_sprout::SproutContext;
+///
+/// let span = info_span!("http_request", request_id = 7);
+/// let _guard = span.enter();
+///
+/// let context = SproutContext::capture();
+/// std::thread::spawn(move || {
+///     context.in_scope(|| info!("This event has the request id"));
+/// })
+/// .join()
+/// .unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct SproutContext {
+    attributes: JsonValue,
+    depths: HashMap<String, usize>,
+    span_id: Option<u64>,
+    root_span_id: Option<u64>,
+    trace_id: Option<u128>,
+    trace_flags: u8,
+    trace_state: Option<String>,
+}
+
+impl Default for SproutContext {
+    fn default() -> Self {
+        Self {
+            attributes: JsonValue::new_object(),
+            depths: HashMap::new(),
+            span_id: None,
+            root_span_id: None,
+            trace_id: None,
+            trace_flags: FLAG_SAMPLED,
+            trace_state: None,
+        }
+    }
+}
+
+impl SproutContext {
+    /// Captures the context of the current span, or of the context attached to this thread if
+    /// there isn't one. If there's neither, the context is empty.
+    pub fn capture() -> Self {
+        with_current_storage(|storage| {
+            // Cloning gives the storage of a child, which only has the attributes it inherits
+            let child = storage.clone();
+            Self {
+                attributes: child.attributes,
+                depths: child.depths,
+                span_id: storage.span_id,
+                root_span_id: storage.root_span_id,
+                trace_id: storage.trace_id,
+                trace_flags: storage.trace_flags,
+                trace_state: storage.trace_state.clone(),
+            }
+        })
+        .or_else(|| with_attached(Self::clone))
+        .unwrap_or_default()
+    }
+
+    /// Reads the context passed down by a parent process through the environment, see
+    /// [`SproutContext::to_env`]. Any variables that are missing or invalid are ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_sprout::SproutContext;
+    ///
+    /// // At the start of the child process
+    /// let _guard = SproutContext::from_env().attach();
+    /// ```
+    pub fn from_env() -> Self {
+        let mut context = Self::default();
+        let attributes = env::var(ATTRIBUTES_ENV)
+            .ok()
+            .and_then(|attributes| json::parse(&attributes).ok())
+            .filter(JsonValue::is_object);
+        if let Some(attributes) = attributes {
+            context.attributes = attributes;
+        }
+        let depths = env::var(DEPTHS_ENV)
+            .ok()
+            .and_then(|depths| json::parse(&depths).ok());
+        if let Some(depths) = depths {
+            context.depths = depths
+                .entries()
+                .filter(|(key, _)| context.attributes.has_key(key))
+                .filter_map(|(key, depth)| Some((key.to_string(), depth.as_usize()?)))
+                .collect();
+        }
+        let trace = env::var(TRACEPARENT_ENV)
+            .ok()
+            .and_then(|traceparent| TraceContext::parse_traceparent(&traceparent));
+        if let Some(trace) = trace {
+            context.span_id = Some(trace.span_id);
+            context.trace_id = Some(trace.trace_id);
+            context.trace_flags = trace.trace_flags;
+            context.trace_state = env::var(TRACESTATE_ENV)
+                .ok()
+                .filter(|s| !s.trim().is_empty());
+        }
+        context
+    }
+
+    /// The environment variables to pass to a child process so it can continue this context,
+    /// `TRACEPARENT` & `TRACESTATE` _(if there's a trace id)_, `SPROUT_CONTEXT` holding the
+    /// attributes as JSON and `SPROUT_CONTEXT_DEPTHS` holding how far each of them has been
+    /// inherited _(if there are [`Inheritance`](crate::Inheritance) policies)_.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::process::Command;
+    /// use tracing_sprout::SproutContext;
+    ///
+    /// let child = Command::new("worker")
+    ///     .envs(SproutContext::capture().to_env())
+    ///     .spawn();
+    /// ```
+    pub fn to_env(&self) -> Vec<(&'static str, String)> {
+        let mut vars = vec![(ATTRIBUTES_ENV, self.attributes.dump())];
+        if !self.depths.is_empty() {
+            let mut depths = JsonValue::new_object();
+            for (key, depth) in &self.depths {
+                insert_lossy(&mut depths, key, *depth);
+            }
+            vars.push((DEPTHS_ENV, depths.dump()));
+        }
+        if let Some(trace) = self.trace_context() {
+            vars.push((TRACEPARENT_ENV, trace.traceparent()));
+            if let Some(trace_state) = trace.trace_state {
+                vars.push((TRACESTATE_ENV, trace_state));
+            }
+        }
+        vars
+    }
+
+    /// The inherited attributes of the captured span
+    pub fn attributes(&self) -> &JsonValue {
+        &self.attributes
+    }
+
+    /// The trace context of the captured span, if trace ids were enabled
+    pub fn trace_context(&self) -> Option<TraceContext> {
+        Some(TraceContext {
+            trace_id: self.trace_id?,
+            span_id: self.span_id?,
+            trace_flags: self.trace_flags,
+            trace_state: self.trace_state.clone(),
+        })
+    }
+
+    /// Attaches the context to this thread until the returned guard is dropped
+    pub fn attach(&self) -> ContextGuard {
+        ATTACHED.with(|attached| {
+            let mut attached = attached.borrow_mut();
+            attached.push(self.clone());
+            ContextGuard {
+                len: attached.len() - 1,
+                _not_send: PhantomData,
+            }
+        })
+    }
+
+    /// Runs the closure with the context attached to this thread
+    pub fn in_scope<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        let _guard = self.attach();
+        f()
+    }
+
+    /// Seeds the storage of a root span, or of an event outside of any span
+    pub(crate) fn apply(&self, storage: &mut SproutStorage) {
+        for (key, value) in self.attributes.entries() {
+            if !storage.attributes.has_key(key) {
+                insert_lossy(&mut storage.attributes, key, value.clone());
+                if let Some(depth) = self.depths.get(key) {
+                    storage.depths.insert(key.to_string(), *depth);
+                }
+            }
+        }
+        storage.span_id = self.span_id;
+        storage.root_span_id = self.root_span_id.or(self.span_id);
+        storage.trace_id = self.trace_id;
+        storage.trace_flags = self.trace_flags;
+        storage.trace_state = self.trace_state.clone();
+    }
+}
+
+/// Detaches the context that was attached with [`SproutContext::attach`] when it's dropped.
+///
+/// The context is attached to the thread it was attached on, so the guard can't be sent to
+/// another.
+#[must_use = "the context is detached as soon as the guard is dropped"]
+#[derive(Debug)]
+pub struct ContextGuard {
+    len: usize,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Drop for ContextGuard {
+    fn drop(&mut self) {
+        // If the thread local has already been destroyed there is nothing left to detach
+        let _ = ATTACHED.try_with(|attached| attached.borrow_mut().truncate(self.len));
+    }
+}
+
+/// Runs the closure against the context attached to this thread, if there is one
+pub(crate) fn with_attached<T>(f: impl FnOnce(&SproutContext) -> T) -> Option<T> {
+    ATTACHED
+        .try_with(|attached| attached.borrow().last().map(f))
+        .ok()
+        .flatten()
+}
@@ -0,0 +1,114 @@
+//! Read and change the fields of the current span at runtime.
+//!
+//! The merged attributes of a span _(its own fields plus the ones it inherited)_ can be read back,
+//! so application code can reuse the context sprout already holds rather than keeping a second
+//! copy of it, for example to return the `request_id` in a response header.
+//!
+//! tracing requires every field of a span to be declared when it's created, but sprout keeps its
+//! own copy of them, so fields can be added to _(or removed from)_ the current span without
+//! declaring them up front. They show up on every record the span writes from then on, and are
+//! inherited by any span or event created within it afterwards _(spans that already exist only
+//! see them with [`TrunkLayer::with_live_inheritance`](crate::TrunkLayer::with_live_inheritance))_.
+//!
+//! This is only possible when the default subscriber is built on top of a
+//! `tracing_subscriber::Registry` with a [`TrunkLayer`](crate::TrunkLayer), otherwise there is
+//! nowhere to keep the fields.
+//!
+//! ```
+//! use tracing::{info, info_span};
+//! use tracing_sprout::current_span;
+//!
+//! let span = info_span!("http_request");
+//! let _guard = span.enter();
+//!
+//! // Once we know who the request is for
+//! current_span::insert("tenant", "acme");
+//! info!("Every record from here on has the tenant");
+//!
+//! let tenant = current_span::get("tenant");
+//! ```
+use json::JsonValue;
+use tracing::Id;
+use tracing_subscriber::registry::SpanRef;
+use tracing_subscriber::Registry;
+
+use crate::storage::{with_span, SproutStorage};
+use crate::util::insert_lossy;
+
+/// The merged attributes of the current span, including the name & version of the application.
+///
+/// These are the attributes the span inherited when it was created plus its own, values recorded
+/// on an ancestor since then aren't included.
+pub fn attributes() -> Option<JsonValue> {
+    with_span(None, read_attributes)
+}
+
+/// The merged attributes of the span with the given id, see [`attributes`]
+///
+/// # Examples
+///
+/// ```
+/// use tracing::info_span;
+/// use tracing_sprout::current_span;
+///
+/// let span = info_span!("http_request", request_id = 7);
+/// if let Some(attributes) = span.id().and_then(|id| current_span::attributes_of(&id)) {
+///     let request_id = attributes["request_id"].as_u64();
+/// }
+/// ```
+pub fn attributes_of(id: &Id) -> Option<JsonValue> {
+    with_span(Some(id), read_attributes)
+}
+
+/// A single merged attribute of the current span, `None` if it doesn't have one
+pub fn get(key: &str) -> Option<JsonValue> {
+    with_span(None, |span| {
+        let extensions = span.extensions();
+        let value = &extensions.get::<SproutStorage>()?.attributes[key];
+        Some(value.clone()).filter(|value| !value.is_null())
+    })
+}
+
+fn read_attributes(span: SpanRef<'_, Registry>) -> Option<JsonValue> {
+    span.extensions()
+        .get::<SproutStorage>()
+        .map(SproutStorage::clone_attributes)
+}
+
+/// Adds a field to the current span, replacing the value if it already has one.
+///
+/// Returns `false` if there isn't a current span that sprout is keeping track of.
+pub fn insert(key: &str, value: impl Into<JsonValue>) -> bool {
+    let value = value.into();
+    with_span(None, |span| {
+        span.extensions_mut()
+            .get_mut::<SproutStorage>()?
+            .record_field(key, value.clone());
+        // The root span only has a canonical log line if they're enabled
+        if let Some(root) = span.scope().from_root().next() {
+            let mut extensions = root.extensions_mut();
+            let canonical = extensions
+                .get_mut::<SproutStorage>()
+                .and_then(|storage| storage.canonical.as_mut());
+            if let Some(canonical) = canonical {
+                insert_lossy(canonical, key, value);
+            }
+        }
+        Some(())
+    })
+    .is_some()
+}
+
+/// Removes a field from the current span, returning its value if it had one.
+///
+/// This removes the field whether it was recorded on the current span or inherited from one of
+/// its ancestors, the ancestors themselves are left as they were. The field stays removed with
+/// [`TrunkLayer::with_live_inheritance`](crate::TrunkLayer::with_live_inheritance), unless it's
+/// recorded on the current span again.
+pub fn remove(key: &str) -> Option<JsonValue> {
+    with_span(None, |span| {
+        span.extensions_mut()
+            .get_mut::<SproutStorage>()?
+            .remove_field(key)
+    })
+}
@@ -0,0 +1,45 @@
+use json::JsonValue;
+
+use std::time::Duration;
+
+/// The unit that durations _(such as `elapsed_time_ms`)_ are written in.
+///
+/// The unit is always stated as a suffix on the key, so a downstream consumer never has to guess
+/// what it's looking at.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum DurationUnit {
+    /// Whole milliseconds, i.e. `elapsed_time_ms: 12`
+    #[default]
+    Millis,
+    /// Whole microseconds, i.e. `elapsed_time_us: 12345`
+    Micros,
+    /// Whole nanoseconds, i.e. `elapsed_time_ns: 12345678`
+    Nanos,
+    /// Fractional milliseconds, i.e. `elapsed_time_ms: 12.345678`
+    FractionalMillis,
+}
+
+impl DurationUnit {
+    fn suffix(&self) -> &str {
+        match *self {
+            Self::Millis | Self::FractionalMillis => "_ms",
+            Self::Micros => "_us",
+            Self::Nanos => "_ns",
+        }
+    }
+
+    /// Appends the unit to the key, i.e. `elapsed_time` becomes `elapsed_time_ms`
+    pub(crate) fn key(&self, base: &str) -> String {
+        format!("{}{}", base, self.suffix())
+    }
+
+    pub(crate) fn value(&self, duration: Duration) -> JsonValue {
+        match *self {
+            Self::Millis => (duration.as_millis() as u64).into(),
+            Self::Micros => (duration.as_micros() as u64).into(),
+            Self::Nanos => (duration.as_nanos() as u64).into(),
+            // Dividing the whole nanoseconds avoids the float noise of scaling up the seconds
+            Self::FractionalMillis => (duration.as_nanos() as f64 / 1_000_000.0).into(),
+        }
+    }
+}
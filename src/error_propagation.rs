@@ -0,0 +1,23 @@
+use json::JsonValue;
+
+/// Which spans are marked as failed when an error is recorded within them.
+///
+/// An error is an `ERROR` level event, or an `error` field that's `true` or a message on either an
+/// event or a span. Failed spans have `error: true` & `status: "error"` added to their `close`
+/// record, which is also raised to `ERROR`.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum ErrorPropagation {
+    /// Spans are never marked as failed
+    #[default]
+    Off,
+    /// Only the span the error was recorded in is marked as failed
+    Span,
+    /// The span the error was recorded in and all of its ancestors are marked as failed
+    Ancestors,
+}
+
+/// Whether the value of an `error` field means something failed, so `error = false` or an empty
+/// message don't count
+pub(crate) fn is_error(value: &JsonValue) -> bool {
+    value.as_bool().unwrap_or(false) || value.as_str().is_some_and(|message| !message.is_empty())
+}
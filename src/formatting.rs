@@ -1,16 +1,33 @@
+use json::{object, JsonValue};
 use std::io::Write;
-use tracing::{Event, Id, Subscriber};
+use tracing::{Event, Id, Level, Subscriber};
 use tracing_core::span::{Attributes, Record};
 use tracing_subscriber::fmt::MakeWriter;
 use tracing_subscriber::layer::Context;
-use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::registry::{LookupSpan, Scope, SpanRef};
 use tracing_subscriber::Layer;
 
-use std::time::Instant;
+use std::cmp;
+use std::collections::HashSet;
+use std::mem;
+use std::sync::Arc;
+use std::time::Duration;
 
 use crate::constants::*;
+use crate::context::with_attached;
+use crate::duration::DurationUnit;
+use crate::error_propagation::{is_error, ErrorPropagation};
+use crate::ids::SpanIdFormat;
+use crate::inheritance::Inheritance;
+use crate::process::{os_thread_id, ProcessFields};
+use crate::propagation::{format_trace_id, generate_span_id, generate_trace_id, TraceContext};
+use crate::providers::{FieldProvider, Providers, RecordTypes};
+use crate::scoped_fields::for_each_field;
+use crate::slow::SlowSpans;
+use crate::span_events::SpanEvents;
+use crate::stats::{SpanStats, SpanStatsSnapshot, StatsKey};
 use crate::storage::SproutStorage;
-use crate::util::{serialize_span, Type};
+use crate::util::{build_span, insert_lossy, serialize_span, serialize_standalone, Type};
 use crate::Result;
 
 /// The subscriber layer, add this to your application's tracing regisitry to initialize it
@@ -25,7 +42,11 @@ use crate::Result;
 /// use tracing_subscriber::{EnvFilter, Registry};
 ///
 /// let env_filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
-/// let formatting_layer = TrunkLayer::new("My Application".to_string(), std::io::stdout);
+/// let formatting_layer = TrunkLayer::new(
+///     "My Application".to_string(),
+///     env!("CARGO_PKG_VERSION").to_string(),
+///     std::io::stdout,
+/// );
 /// let subscriber = Registry::default()
 ///      .with(env_filter)
 ///      .with(formatting_layer);
@@ -33,9 +54,28 @@ use crate::Result;
 /// set_global_default(subscriber).expect("failed to set up global tracing subscriber")
 /// ```
 pub struct TrunkLayer<W: for<'a> MakeWriter<'a> + 'static> {
-    writer: W,
-    version: String,
-    name: String,
+    writer: Arc<W>,
+    /// The attributes every root span starts with
+    static_fields: JsonValue,
+    process_fields: ProcessFields,
+    providers: Providers,
+    span_list: bool,
+    span_id_format: SpanIdFormat,
+    trace_ids: bool,
+    span_events: SpanEvents,
+    duration_unit: DurationUnit,
+    slow_spans: Option<SlowSpans>,
+    span_stats: Option<SpanStats>,
+    event_counts: bool,
+    error_propagation: ErrorPropagation,
+    canonical_log_line: bool,
+    event_records: bool,
+    tree_documents: bool,
+    live_inheritance: bool,
+    inheritance: Option<Arc<Inheritance>>,
+    record_diff: bool,
+    #[cfg(feature = "metrics")]
+    metrics: Option<crate::metrics_bridge::MetricsBridge>,
 }
 
 impl<W> TrunkLayer<W>
@@ -49,15 +89,482 @@ where
     /// ```
     /// use tracing_sprout::TrunkLayer;
     ///
-    /// let layer = TrunkLayer::new("I am Groot".to_string(), std::io::stdout);
+    /// let layer = TrunkLayer::new("I am Groot".to_string(), "0.1.0".to_string(), std::io::stdout);
     /// ```
     pub fn new(name: String, version: String, writer: W) -> Self {
+        let static_fields = object! {
+            "name": name,
+            "version": version
+        };
         Self {
-            writer,
-            version,
-            name,
+            writer: Arc::new(writer),
+            static_fields,
+            process_fields: ProcessFields::NONE,
+            providers: Providers::default(),
+            span_list: false,
+            span_id_format: SpanIdFormat::default(),
+            trace_ids: false,
+            span_events: SpanEvents::default(),
+            duration_unit: DurationUnit::default(),
+            slow_spans: None,
+            span_stats: None,
+            event_counts: false,
+            error_propagation: ErrorPropagation::default(),
+            canonical_log_line: false,
+            event_records: true,
+            tree_documents: false,
+            live_inheritance: false,
+            inheritance: None,
+            record_diff: false,
+            #[cfg(feature = "metrics")]
+            metrics: None,
         }
     }
+
+    /// Add a field with a fixed value to every record, such as the environment, region or git
+    /// sha. The field is seeded into every root span, so it can be overwritten by a span or event
+    /// that records the same field.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_sprout::TrunkLayer;
+    ///
+    /// let layer = TrunkLayer::new("I am Groot".to_string(), "0.1.0".to_string(), std::io::stdout)
+    ///     .with_static_field("environment", "production")
+    ///     .with_static_field("region", "eu-west-1");
+    /// ```
+    pub fn with_static_field(mut self, key: &str, value: impl Into<JsonValue>) -> Self {
+        insert_lossy(&mut self.static_fields, key, value);
+        self.sync_static_fields();
+        self
+    }
+
+    /// Add fields describing the process to every record, see [`ProcessFields`]
+    ///
+    /// Defaults to [`ProcessFields::NONE`]
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_sprout::{ProcessFields, TrunkLayer};
+    ///
+    /// let layer = TrunkLayer::new("I am Groot".to_string(), "0.1.0".to_string(), std::io::stdout)
+    ///     .with_process_fields(ProcessFields::PID | ProcessFields::HOSTNAME);
+    /// ```
+    pub fn with_process_fields(mut self, process_fields: ProcessFields) -> Self {
+        process_fields.insert_static(&mut self.static_fields);
+        self.process_fields = process_fields;
+        self.sync_static_fields();
+        self
+    }
+
+    /// Register a provider that adds fields to every record it applies to as it's written, see
+    /// [`FieldProvider`]. Providers run in the order they were registered.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_sprout::{JsonValue, TrunkLayer};
+    ///
+    /// let layer = TrunkLayer::new("I am Groot".to_string(), "0.1.0".to_string(), std::io::stdout)
+    ///     .with_field_provider(|fields: &mut JsonValue| {
+    ///         let _ = fields.insert("written_at_unix", 1_700_000_000);
+    ///     });
+    /// ```
+    pub fn with_field_provider(mut self, provider: impl FieldProvider + 'static) -> Self {
+        let record_types = provider.record_types();
+        self.providers.push(record_types, Box::new(provider));
+        self
+    }
+
+    /// Register a provider that only applies to the given types of record, regardless of the ones
+    /// it says it applies to
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_sprout::{JsonValue, RecordTypes, TrunkLayer};
+    ///
+    /// let layer = TrunkLayer::new("I am Groot".to_string(), "0.1.0".to_string(), std::io::stdout)
+    ///     .with_field_provider_for(RecordTypes::EVENT, |fields: &mut JsonValue| {
+    ///         let _ = fields.insert("task_id", 7);
+    ///     });
+    /// ```
+    pub fn with_field_provider_for(
+        mut self,
+        record_types: RecordTypes,
+        provider: impl FieldProvider + 'static,
+    ) -> Self {
+        self.providers.push(record_types, Box::new(provider));
+        self
+    }
+
+    /// Attach the full span stack to every record.
+    ///
+    /// When enabled each record gets a `spans` array, ordered from the root span down, holding
+    /// every ancestor's name, id and **own** fields _(not the merged ones)_, as well as a
+    /// `span_path` such as `http_request::db_query`. This is useful for working out which level
+    /// a field came from when the same key is recorded at several levels.
+    ///
+    /// Defaults to `false`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_sprout::TrunkLayer;
+    ///
+    /// let layer = TrunkLayer::new("I am Groot".to_string(), "0.1.0".to_string(), std::io::stdout)
+    ///     .with_span_list(true);
+    /// ```
+    pub fn with_span_list(mut self, span_list: bool) -> Self {
+        self.span_list = span_list;
+        self
+    }
+
+    /// Choose how the `span_id`, `parent_span_id` and `root_span_id` are written on each record,
+    /// see [`SpanIdFormat`] for the options
+    ///
+    /// Defaults to [`SpanIdFormat::Tracing`]
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_sprout::{SpanIdFormat, TrunkLayer};
+    ///
+    /// let layer = TrunkLayer::new("I am Groot".to_string(), "0.1.0".to_string(), std::io::stdout)
+    ///     .with_span_id_format(SpanIdFormat::Hex);
+    /// ```
+    pub fn with_span_id_format(mut self, format: SpanIdFormat) -> Self {
+        self.span_id_format = format;
+        self
+    }
+
+    /// Choose which points in each span's lifecycle produce a record, see [`SpanEvents`]
+    ///
+    /// Defaults to `SpanEvents::NEW | SpanEvents::CLOSE`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_sprout::{SpanEvents, TrunkLayer};
+    ///
+    /// // Only write a record once each span has finished
+    /// let layer = TrunkLayer::new("I am Groot".to_string(), "0.1.0".to_string(), std::io::stdout)
+    ///     .with_span_events(SpanEvents::CLOSE);
+    /// ```
+    pub fn with_span_events(mut self, span_events: SpanEvents) -> Self {
+        self.span_events = span_events;
+        self
+    }
+
+    /// Choose the unit that durations are written in, see [`DurationUnit`]. The unit is always
+    /// part of the key, so `elapsed_time_ms` becomes `elapsed_time_us` when using
+    /// [`DurationUnit::Micros`].
+    ///
+    /// Defaults to [`DurationUnit::Millis`], which will show most short lived spans _(such as
+    /// database or cache calls)_ as `0`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_sprout::{DurationUnit, TrunkLayer};
+    ///
+    /// let layer = TrunkLayer::new("I am Groot".to_string(), "0.1.0".to_string(), std::io::stdout)
+    ///     .with_duration_unit(DurationUnit::FractionalMillis);
+    /// ```
+    pub fn with_duration_unit(mut self, duration_unit: DurationUnit) -> Self {
+        self.duration_unit = duration_unit;
+        if let Some(span_stats) = &self.span_stats {
+            span_stats.set_duration_unit(duration_unit);
+        }
+        self
+    }
+
+    /// Flag spans that take longer than the given thresholds, see [`SlowSpans`]
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use tracing_sprout::{SlowSpans, TrunkLayer};
+    ///
+    /// let layer = TrunkLayer::new("I am Groot".to_string(), "0.1.0".to_string(), std::io::stdout)
+    ///     .with_slow_spans(SlowSpans::new().with_default(Duration::from_millis(250)));
+    /// ```
+    pub fn with_slow_spans(mut self, slow_spans: SlowSpans) -> Self {
+        self.slow_spans = Some(slow_spans);
+        self
+    }
+
+    /// Report how many events happened within each span on its `close` record, broken down by
+    /// level and including the events of its descendants, along with the message of the first
+    /// `ERROR` event. This makes it possible to spot a failing request from its `close` record
+    /// alone.
+    ///
+    /// Defaults to `false`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_sprout::TrunkLayer;
+    ///
+    /// let layer = TrunkLayer::new("I am Groot".to_string(), "0.1.0".to_string(), std::io::stdout)
+    ///     .with_event_counts(true);
+    /// ```
+    pub fn with_event_counts(mut self, event_counts: bool) -> Self {
+        self.event_counts = event_counts;
+        self
+    }
+
+    /// Mark spans as failed when an error is recorded within them, see [`ErrorPropagation`]
+    ///
+    /// Defaults to [`ErrorPropagation::Off`]
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_sprout::{ErrorPropagation, TrunkLayer};
+    ///
+    /// let layer = TrunkLayer::new("I am Groot".to_string(), "0.1.0".to_string(), std::io::stdout)
+    ///     .with_error_propagation(ErrorPropagation::Ancestors);
+    /// ```
+    pub fn with_error_propagation(mut self, error_propagation: ErrorPropagation) -> Self {
+        self.error_propagation = error_propagation;
+        self
+    }
+
+    /// Write a [canonical log line](https://stripe.com/blog/canonical-log-lines) for every root
+    /// span.
+    ///
+    /// Every field recorded by the root span, its descendants and the events within them is
+    /// collected into one wide `canonical` record, which is written when the root span closes
+    /// along with its timings, event counts and error status. Fields recorded later, or deeper in
+    /// the tree, take precedence when there are collisions.
+    ///
+    /// Combined with [`SpanEvents::NONE`] and [`TrunkLayer::with_event_records`] this leaves a
+    /// single record per request.
+    ///
+    /// Defaults to `false`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_sprout::{SpanEvents, TrunkLayer};
+    ///
+    /// let layer = TrunkLayer::new("I am Groot".to_string(), "0.1.0".to_string(), std::io::stdout)
+    ///     .with_canonical_log_line(true)
+    ///     .with_span_events(SpanEvents::NONE)
+    ///     .with_event_records(false);
+    /// ```
+    pub fn with_canonical_log_line(mut self, canonical_log_line: bool) -> Self {
+        self.canonical_log_line = canonical_log_line;
+        self
+    }
+
+    /// Whether a record is written for each event. Turning this off is only really useful
+    /// alongside [`TrunkLayer::with_canonical_log_line`], as the events are still collected into
+    /// it.
+    ///
+    /// Defaults to `true`
+    pub fn with_event_records(mut self, event_records: bool) -> Self {
+        self.event_records = event_records;
+        self
+    }
+
+    /// Write every root span as one nested JSON document once it closes, rather than writing
+    /// each record as it happens.
+    ///
+    /// Each span in the document is its close record _(with its timings and when it started
+    /// relative to the root span)_, along with an `events` array of its events and any of the
+    /// lifecycle records enabled with [`TrunkLayer::with_span_events`] in the order they happened,
+    /// and a `children` array of its child spans in the order they closed. Events emitted outside
+    /// of any span are still written straight away.
+    ///
+    /// As everything within a root span is held in memory until it closes this is intended for
+    /// offline debugging, rather than long-lived spans in production.
+    ///
+    /// Defaults to `false`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_sprout::TrunkLayer;
+    ///
+    /// let layer = TrunkLayer::new("I am Groot".to_string(), "0.1.0".to_string(), std::io::stdout)
+    ///     .with_tree_documents(true);
+    /// ```
+    pub fn with_tree_documents(mut self, tree_documents: bool) -> Self {
+        self.tree_documents = tree_documents;
+        self
+    }
+
+    /// Set the policies for which attributes are inherited by descendant spans & events, and
+    /// what happens when a child records an attribute it has already inherited.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_sprout::{Collision, Inherit, Inheritance, TrunkLayer};
+    ///
+    /// let layer = TrunkLayer::new("I am Groot".to_string(), "0.1.0".to_string(), std::io::stdout)
+    ///     .with_inheritance(
+    ///         Inheritance::new()
+    ///             .with_prefix("_", Inherit::Never)
+    ///             .with_collision(Collision::KeepBoth),
+    ///     );
+    /// ```
+    pub fn with_inheritance(mut self, inheritance: Inheritance) -> Self {
+        self.inheritance = Some(Arc::new(inheritance));
+        self
+    }
+
+    /// Inherit the attributes of ancestors as they are when each record is written, rather than
+    /// when the span was created.
+    ///
+    /// By default a span takes a copy of its parent's attributes when it's created, so a value
+    /// recorded on the parent afterwards _(i.e. `Span::current().record("user_id", ..)` once a
+    /// request has been authenticated)_ never shows up in the records of children that were
+    /// already created. With this enabled every record re-merges the attributes of each span in
+    /// its scope from the root down, at the cost of doing so for every record.
+    ///
+    /// Defaults to `false`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_sprout::TrunkLayer;
+    ///
+    /// let layer = TrunkLayer::new("I am Groot".to_string(), "0.1.0".to_string(), std::io::stdout)
+    ///     .with_live_inheritance(true);
+    /// ```
+    pub fn with_live_inheritance(mut self, live_inheritance: bool) -> Self {
+        self.live_inheritance = live_inheritance;
+        self
+    }
+
+    /// Include the value each field had before it was recorded in the records written for
+    /// [`SpanEvents::RECORD`], as a `changes` object of `{ "before": .., "after": .. }` per field.
+    /// The value before is the one the span would have written, so it can be inherited.
+    ///
+    /// Defaults to `false`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_sprout::{SpanEvents, TrunkLayer};
+    ///
+    /// let layer = TrunkLayer::new("I am Groot".to_string(), "0.1.0".to_string(), std::io::stdout)
+    ///     .with_span_events(SpanEvents::default() | SpanEvents::RECORD)
+    ///     .with_record_diff(true);
+    /// ```
+    pub fn with_record_diff(mut self, record_diff: bool) -> Self {
+        self.record_diff = record_diff;
+        self
+    }
+
+    /// Aggregate the duration of every span into latency statistics, see [`SpanStats`]
+    ///
+    /// The summary records are written to the same writer as every other record.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use tracing_sprout::{SpanStats, TrunkLayer};
+    ///
+    /// let stats = SpanStats::new().with_interval(Duration::from_secs(60));
+    /// let handle = stats.handle();
+    /// let layer = TrunkLayer::new("I am Groot".to_string(), "0.1.0".to_string(), std::io::stdout)
+    ///     .with_span_stats(stats);
+    /// ```
+    pub fn with_span_stats(mut self, span_stats: SpanStats) -> Self
+    where
+        W: Send + Sync,
+    {
+        let writer = self.writer.clone();
+        span_stats.set_duration_unit(self.duration_unit);
+        span_stats.set_static_fields(self.static_fields.clone());
+        span_stats.set_sink(Box::new(move |snapshot, duration_unit, static_fields| {
+            for stats in snapshot {
+                let record = summary_record(static_fields, stats, duration_unit);
+                if let Ok(mut bytes) = serialize_standalone(record, &stats.key, Type::Summary) {
+                    bytes.push(b'\n');
+                    let _ = writer.make_writer().write_all(&bytes);
+                }
+            }
+        }));
+        self.span_stats = Some(span_stats);
+        self
+    }
+
+    /// Record span durations and event counts through the [metrics](https://docs.rs/metrics)
+    /// facade, so that whichever recorder the application installs _(Prometheus, StatsD etc.)_
+    /// receives them.
+    ///
+    /// - Every span close records its lifetime, in seconds, to the `span.duration` histogram,
+    ///   labelled with the span name and each of the given inherited attributes that the span has
+    /// - Every event increments the `events` counter, labelled with the level and target
+    ///
+    /// Bear in mind every distinct combination of labels is its own time series, so keep the
+    /// attributes to ones with a small number of possible values _(i.e. `http.route` rather than
+    /// `user_id`)_
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_sprout::TrunkLayer;
+    ///
+    /// let layer = TrunkLayer::new("I am Groot".to_string(), "0.1.0".to_string(), std::io::stdout)
+    ///     .with_metrics(["http.route", "http.method"]);
+    /// ```
+    #[cfg(feature = "metrics")]
+    pub fn with_metrics<I, L>(mut self, labels: I) -> Self
+    where
+        I: IntoIterator<Item = L>,
+        L: AsRef<str>,
+    {
+        let labels = labels.into_iter().map(|l| l.as_ref().to_string()).collect();
+        self.metrics = Some(crate::metrics_bridge::MetricsBridge::new(labels));
+        self
+    }
+
+    /// Give every trace a 128-bit `trace_id`, which is written on every record.
+    ///
+    /// The trace id is established when a root span is created. If the root span has a
+    /// `traceparent` field _(and optionally a `tracestate` field)_ holding a valid
+    /// [W3C Trace Context](https://www.w3.org/TR/trace-context/) value, that trace is continued,
+    /// otherwise a new trace id is generated. Every descendant span inherits it, and the
+    /// current context can be retrieved with [`TraceContext::current`] to pass on to other
+    /// services _(see [`crate::propagation`])_.
+    ///
+    /// With the `opentelemetry` feature enabled, spans that are tracked by an `OpenTelemetryLayer`
+    /// _(which must be added to the registry before this layer)_ always use the OpenTelemetry
+    /// trace id and span id instead, whether or not this is enabled.
+    ///
+    /// The spec requires span ids to be unique across every service in the trace, so with this
+    /// enabled each span is given a random 64-bit id rather than one assigned as configured with
+    /// [`TrunkLayer::with_span_id_format`]. These are written as 16 character hex strings, just
+    /// like the span ids that are propagated.
+    ///
+    /// Once an incoming trace has been adopted, the `traceparent` & `tracestate` fields are
+    /// removed from the root span, and the caller's span id is written as its `parent_span_id`.
+    ///
+    /// Defaults to `false`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_sprout::TrunkLayer;
+    ///
+    /// let layer = TrunkLayer::new("I am Groot".to_string(), "0.1.0".to_string(), std::io::stdout)
+    ///     .with_trace_ids(true);
+    /// ```
+    pub fn with_trace_ids(mut self, trace_ids: bool) -> Self {
+        self.trace_ids = trace_ids;
+        self
+    }
+
     /// Given a serialized byte array, this function will add a `\n` byte to the end and
     /// then flush the bytes into the writer that was provided when initializing the layer.
     ///
@@ -69,6 +576,401 @@ where
         self.writer.make_writer().write_all(&buffer)?;
         Ok(())
     }
+
+    /// Passes the static fields on to the summary records, which aren't written by the layer
+    fn sync_static_fields(&self) {
+        if let Some(span_stats) = &self.span_stats {
+            span_stats.set_static_fields(self.static_fields.clone());
+        }
+    }
+
+    /// The storage that every root span, and every event outside of a span, starts from
+    fn root_storage(&self) -> SproutStorage {
+        let mut storage = SproutStorage::new(self.static_fields.clone());
+        storage.inheritance = self.inheritance.clone();
+        with_attached(|context| context.apply(&mut storage));
+        storage
+    }
+
+    /// The id of a new span, a random one when trace ids are enabled as those are propagated to
+    /// other services
+    fn new_span_id(&self, id: &Id) -> u64 {
+        if self.trace_ids {
+            generate_span_id()
+        } else {
+            self.span_id_format.assign(id)
+        }
+    }
+
+    /// Joins the trace of the incoming `traceparent` recorded on the span, or starts a new one
+    fn start_trace(&self, storage: &mut SproutStorage) {
+        let incoming = storage.fields[TRACEPARENT]
+            .as_str()
+            .and_then(TraceContext::parse_traceparent);
+        match incoming {
+            Some(context) => {
+                storage.trace_id = Some(context.trace_id);
+                storage.trace_flags = context.trace_flags;
+                storage.trace_state = storage.fields[TRACESTATE]
+                    .as_str()
+                    .filter(|s| !s.trim().is_empty())
+                    .map(String::from);
+                // The caller's span is the remote parent of the root span
+                storage.parent_span_id = Some(context.span_id);
+                // Now they've been adopted, there's no need to write them on every record
+                storage.remove_field(TRACEPARENT);
+                storage.remove_field(TRACESTATE);
+            }
+            None => storage.trace_id = Some(generate_trace_id()),
+        }
+    }
+
+    /// Writes a record for a point in the span's lifecycle, or holds on to it until the root span
+    /// closes when writing whole trees.
+    ///
+    /// The closure is given the span's storage and the attributes that are about to be written,
+    /// so that any record specific fields can be added.
+    fn emit_span_record<S>(
+        &self,
+        span: &SpanRef<'_, S>,
+        span_type: Type,
+        f: impl FnOnce(&mut SproutStorage, &mut JsonValue, &mut Level),
+    ) where
+        S: for<'a> LookupSpan<'a>,
+    {
+        if let Some(record) = self.build_span_record(span, span_type, None, f) {
+            self.emit_built_span_record(span, span_type, record);
+        }
+    }
+
+    /// Writes a record built by [`TrunkLayer::build_span_record`], or holds on to it until the
+    /// root span closes when writing whole trees
+    fn emit_built_span_record<S>(&self, span: &SpanRef<'_, S>, span_type: Type, record: JsonValue)
+    where
+        S: for<'a> LookupSpan<'a>,
+    {
+        if self.tree_documents && !matches!(span_type, Type::Canonical) {
+            if let Some(visitor) = span.extensions_mut().get_mut::<SproutStorage>() {
+                visitor.tree.events.push(record);
+            }
+        } else {
+            let _ = self.emit(record.dump().into_bytes());
+        }
+    }
+
+    /// Builds the record for a point in the span's lifecycle, see [`TrunkLayer::emit_span_record`]
+    ///
+    /// The record starts from `base` when it's given, rather than all of the span's attributes.
+    fn build_span_record<S>(
+        &self,
+        span: &SpanRef<'_, S>,
+        span_type: Type,
+        base: Option<JsonValue>,
+        f: impl FnOnce(&mut SproutStorage, &mut JsonValue, &mut Level),
+    ) -> Option<JsonValue>
+    where
+        S: for<'a> LookupSpan<'a>,
+    {
+        let mut level = *span.metadata().level();
+        let base = base.or_else(|| self.live_attributes(Some(span.scope()), None));
+        let attributes = {
+            let mut extensions = span.extensions_mut();
+            extensions.get_mut::<SproutStorage>().map(|visitor| {
+                let mut attributes = base.unwrap_or_else(|| visitor.clone_attributes());
+                self.insert_ids(&mut attributes, visitor);
+                self.insert_os_thread_id(&mut attributes);
+                f(visitor, &mut attributes, &mut level);
+                attributes
+            })
+        };
+        if let Some(mut attributes) = attributes {
+            self.providers.provide(span_type, &mut attributes);
+            self.insert_span_list(&mut attributes, Some(span.scope()));
+            build_span(attributes, span.metadata(), &level, span_type).ok()
+        } else {
+            tracing::error!(target: "sprout",
+                "Expected to find Sprout Storage located in the span when writing its record. This is likely a bug"
+            );
+            None
+        }
+    }
+
+    /// Nests the events and children buffered by the span within its close record, then hands
+    /// it to the parent span, or writes the whole document if it's the root
+    fn close_tree_node<S>(&self, span: &SpanRef<'_, S>, mut node: JsonValue)
+    where
+        S: for<'a> LookupSpan<'a>,
+    {
+        let tree = {
+            let mut extensions = span.extensions_mut();
+            extensions.get_mut::<SproutStorage>().map(|visitor| {
+                if let Some(root_created_at) = visitor.root_created_at {
+                    let offset = visitor.timings.created_at.duration_since(root_created_at);
+                    self.insert_duration(&mut node, TIME_SINCE_ROOT, offset);
+                }
+                mem::take(&mut visitor.tree)
+            })
+        };
+        let tree = tree.unwrap_or_default();
+        insert_lossy(&mut node, TREE_EVENTS, JsonValue::Array(tree.events));
+        insert_lossy(&mut node, TREE_CHILDREN, JsonValue::Array(tree.children));
+        match span.parent() {
+            Some(parent) => {
+                if let Some(visitor) = parent.extensions_mut().get_mut::<SproutStorage>() {
+                    visitor.tree.children.push(node);
+                }
+            }
+            None => {
+                let _ = self.emit(node.dump().into_bytes());
+            }
+        }
+    }
+
+    /// Marks the first span in the scope as failed, and the rest of them if the errors propagate
+    /// to the ancestors
+    fn mark_errored<S>(&self, scope: Option<Scope<'_, S>>)
+    where
+        S: for<'a> LookupSpan<'a>,
+    {
+        let depth = match self.error_propagation {
+            ErrorPropagation::Off => return,
+            ErrorPropagation::Span => 1,
+            ErrorPropagation::Ancestors => usize::MAX,
+        };
+        for span in scope.into_iter().flatten().take(depth) {
+            if let Some(storage) = span.extensions_mut().get_mut::<SproutStorage>() {
+                storage.errored = true;
+            }
+        }
+    }
+
+    /// Adds the timings, event counts, error status and slowness of a span that is closing
+    fn insert_close_fields(
+        &self,
+        visitor: &SproutStorage,
+        attributes: &mut JsonValue,
+        level: &mut Level,
+        exceeded: Option<Duration>,
+        event_counts: bool,
+    ) {
+        let timings = &visitor.timings;
+        self.insert_duration(attributes, ELAPSED, timings.lifetime());
+        self.insert_duration(attributes, BUSY, timings.busy());
+        self.insert_duration(attributes, IDLE, timings.idle());
+        insert_lossy(attributes, ENTER_COUNT, timings.enter_count);
+        if event_counts {
+            let counts = &visitor.event_counts;
+            insert_lossy(attributes, EVENT_COUNTS, counts.to_json());
+            if let Some(first_error) = &counts.first_error {
+                insert_lossy(attributes, FIRST_ERROR, first_error.as_str());
+            }
+        }
+        if visitor.errored {
+            if !is_error(&attributes[ERROR]) {
+                insert_lossy(attributes, ERROR, true);
+            }
+            insert_lossy(attributes, STATUS, STATUS_ERROR);
+            *level = Level::ERROR;
+        }
+        if let (Some(slow_spans), Some(threshold)) = (&self.slow_spans, exceeded) {
+            insert_lossy(attributes, SLOW, true);
+            self.insert_duration(attributes, SLOW_THRESHOLD, threshold);
+            *level = cmp::min(*level, slow_spans.level());
+        }
+    }
+
+    /// Merges the fields into the canonical log line of the root span of the scope
+    fn merge_into_canonical<S>(&self, scope: Option<Scope<'_, S>>, fields: &JsonValue)
+    where
+        S: for<'a> LookupSpan<'a>,
+    {
+        if !self.canonical_log_line {
+            return;
+        }
+        let root = match scope.and_then(|scope| scope.from_root().next()) {
+            Some(root) => root,
+            None => return,
+        };
+        let mut extensions = root.extensions_mut();
+        let canonical = extensions
+            .get_mut::<SproutStorage>()
+            .and_then(|storage| storage.canonical.as_mut());
+        if let Some(canonical) = canonical {
+            for (key, value) in fields.entries() {
+                // Every event has its own message, none of them describe the whole request
+                if key != "message" {
+                    insert_lossy(canonical, key, value.clone());
+                }
+            }
+        }
+    }
+
+    /// Merges the attributes of every span in the scope as they are now, rather than when each
+    /// span was created, so that values recorded on an ancestor later on are included. `own` are
+    /// the fields of an event within the scope. `None` unless live inheritance is enabled, or if
+    /// there isn't a scope.
+    ///
+    /// This takes a read lock on the extensions of every span in the scope, so it must not be
+    /// called while holding a write lock on any of them.
+    fn live_attributes<S>(
+        &self,
+        scope: Option<Scope<'_, S>>,
+        own: Option<&JsonValue>,
+    ) -> Option<JsonValue>
+    where
+        S: for<'a> LookupSpan<'a>,
+    {
+        if !self.live_inheritance {
+            return None;
+        }
+        let mut spans = scope?.from_root();
+        // The root holds everything that isn't recorded on a span, such as the name & version
+        let (mut attributes, mut depths) = spans
+            .next()?
+            .extensions()
+            .get::<SproutStorage>()
+            .map(|storage| (storage.clone_attributes(), storage.depths.clone()))?;
+        let mut merge = |fields: &JsonValue, removed: Option<&HashSet<String>>| {
+            match &self.inheritance {
+                Some(inheritance) => {
+                    inheritance.descend(&mut attributes, &mut depths);
+                    for (key, value) in fields.entries() {
+                        inheritance
+                            .insert(&mut attributes, &mut depths, key, value.clone())
+                            .ok();
+                    }
+                }
+                None => {
+                    for (key, value) in fields.entries() {
+                        insert_lossy(&mut attributes, key, value.clone());
+                    }
+                }
+            }
+            // Fields removed from a span stay removed, even though an ancestor still has them
+            for key in removed.into_iter().flatten() {
+                attributes.remove(key);
+                depths.remove(key);
+            }
+        };
+        for span in spans {
+            if let Some(storage) = span.extensions().get::<SproutStorage>() {
+                merge(&storage.fields, Some(&storage.removed));
+            }
+        }
+        if let Some(own) = own {
+            merge(own, None);
+        }
+        Some(attributes)
+    }
+
+    /// Adds a duration in the configured unit, with the unit appended to the key
+    fn insert_duration(&self, attributes: &mut JsonValue, key: &str, duration: Duration) {
+        insert_lossy(
+            attributes,
+            &self.duration_unit.key(key),
+            self.duration_unit.value(duration),
+        );
+    }
+
+    /// Adds the identifiers of the span that the storage belongs to, if enabled
+    fn insert_ids(&self, attributes: &mut JsonValue, storage: &SproutStorage) {
+        if let Some(trace_id) = storage.trace_id {
+            insert_lossy(attributes, TRACE_ID, format_trace_id(trace_id));
+            insert_lossy(
+                attributes,
+                TRACE_FLAGS,
+                format!("{:02x}", storage.trace_flags),
+            );
+        }
+        if self.span_id_format == SpanIdFormat::Disabled {
+            return;
+        }
+        let ids = [
+            (SPAN_ID_KEY, storage.span_id),
+            (PARENT_SPAN_ID, storage.parent_span_id),
+            (ROOT_SPAN_ID, storage.root_span_id),
+        ];
+        // Spans within a trace have random ids, or ones from the caller or OpenTelemetry
+        let random = storage.trace_id.is_some();
+        for (key, id) in ids.iter() {
+            if let Some(id) = id {
+                insert_lossy(attributes, key, self.span_id_format.render(*id, random));
+            }
+        }
+    }
+
+    /// Adds the id the operating system gave the current thread, if enabled
+    fn insert_os_thread_id(&self, attributes: &mut JsonValue) {
+        if !self.process_fields.contains(ProcessFields::OS_THREAD_ID) {
+            return;
+        }
+        if let Some(id) = os_thread_id() {
+            insert_lossy(attributes, OS_THREAD_ID, id);
+        }
+    }
+
+    /// Adds the `spans` array and the `span_path` to the attributes, if enabled.
+    ///
+    /// This takes a read lock on the extensions of every span in the scope, so it must not be
+    /// called while holding a write lock on any of them.
+    fn insert_span_list<S>(&self, attributes: &mut JsonValue, scope: Option<Scope<'_, S>>)
+    where
+        S: for<'a> LookupSpan<'a>,
+    {
+        if !self.span_list {
+            return;
+        }
+        let mut spans = JsonValue::new_array();
+        let mut path: Vec<&str> = Vec::new();
+        for span in scope.into_iter().flat_map(|scope| scope.from_root()) {
+            let (id, random, fields) = span
+                .extensions()
+                .get::<SproutStorage>()
+                .map(|storage| {
+                    let random = storage.trace_id.is_some();
+                    (storage.span_id, random, storage.fields.clone())
+                })
+                .unwrap_or_else(|| (None, false, JsonValue::new_object()));
+            let id = id.unwrap_or_else(|| span.id().into_u64());
+            path.push(span.name());
+            spans
+                .push(object! {
+                    SPAN_LIST_NAME => span.name(),
+                    SPAN_LIST_ID => self.span_id_format.render(id, random),
+                    SPAN_LIST_FIELDS => fields,
+                })
+                .ok();
+        }
+        insert_lossy(attributes, SPANS, spans);
+        insert_lossy(attributes, SPAN_PATH, path.join(SPAN_PATH_SEPARATOR));
+    }
+}
+
+/// The attributes of the summary record for a single span's statistics
+fn summary_record(
+    static_fields: &JsonValue,
+    stats: &SpanStatsSnapshot,
+    duration_unit: DurationUnit,
+) -> JsonValue {
+    let mut record = static_fields.clone();
+    insert_lossy(&mut record, STATS_SPAN, stats.key.as_str());
+    insert_lossy(&mut record, STATS_COUNT, stats.count);
+    let durations = [
+        (STATS_MIN, stats.min),
+        (STATS_MAX, stats.max),
+        (STATS_MEAN, stats.mean),
+        (STATS_P50, stats.p50),
+        (STATS_P90, stats.p90),
+        (STATS_P99, stats.p99),
+    ];
+    for (key, duration) in durations.iter() {
+        insert_lossy(
+            &mut record,
+            &duration_unit.key(key),
+            duration_unit.value(*duration),
+        );
+    }
+    record
 }
 
 impl<S, W> Layer<S> for TrunkLayer<W>
@@ -84,22 +986,51 @@ where
                 extensions
                     .get_mut::<SproutStorage>()
                     .cloned()
-                    .unwrap_or_else(|| SproutStorage::new(&self.name, &self.version))
+                    .unwrap_or_else(|| self.root_storage())
             } else {
-                SproutStorage::new(&self.name, &self.version)
+                self.root_storage()
             };
 
-            let mut extensions = span.extensions_mut();
-
             // Register all fields.
             // Fields on the new span should override fields on the parent span if there is a conflict.
             attrs.record(&mut visitor);
-            let attributes = visitor.clone_attributes();
+            #[cfg(feature = "opentelemetry")]
+            let span_id = match crate::otel::otel_ids(&span) {
+                Some(otel) => {
+                    visitor.trace_id = Some(otel.trace_id);
+                    visitor.trace_flags = otel.trace_flags;
+                    otel.span_id
+                }
+                None => self.new_span_id(id),
+            };
+            #[cfg(not(feature = "opentelemetry"))]
+            let span_id = self.new_span_id(id);
+            visitor.assign_span_id(span_id);
+            let created_at = visitor.timings.created_at;
+            visitor.root_created_at.get_or_insert(created_at);
+            if self.trace_ids && visitor.trace_id.is_none() {
+                self.start_trace(&mut visitor);
+            }
+            let errored = is_error(&visitor.fields[ERROR]);
+            let fields = if !self.canonical_log_line {
+                None
+            } else if span.parent().is_none() {
+                visitor.canonical = Some(visitor.clone_attributes());
+                None
+            } else {
+                Some(visitor.fields.clone())
+            };
             // Associate the visitor with the Span for future usage via the Span's extensions
-            extensions.insert(visitor);
+            span.extensions_mut().insert(visitor);
+            if let Some(fields) = fields {
+                self.merge_into_canonical(Some(span.scope()), &fields);
+            }
+            if errored {
+                self.mark_errored(Some(span.scope()));
+            }
 
-            if let Ok(serialized) = serialize_span(attributes, span.metadata(), Type::Enter) {
-                let _ = self.emit(serialized);
+            if self.span_events.contains(SpanEvents::NEW) {
+                self.emit_span_record(&span, Type::New, |_, _, _| ());
             }
         } else {
             tracing::error!(target: "sprout", "Expected to find Span ID when creating a new span. This is likely a bug");
@@ -108,11 +1039,74 @@ where
 
     fn on_record(&self, span: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
         if let Some(span) = ctx.span(span) {
-            let mut extensions = span.extensions_mut();
-            if let Some(visitor) = extensions.get_mut::<SproutStorage>() {
-                values.record(visitor);
+            let update = self.span_events.contains(SpanEvents::RECORD);
+            let recorded = if self.canonical_log_line || update {
+                let mut recorded = SproutStorage::new(JsonValue::new_object());
+                values.record(&mut recorded);
+                self.merge_into_canonical(Some(span.scope()), &recorded.fields);
+                Some(recorded.fields)
             } else {
-                tracing::error!(target: "sprout", "Expected to find Sprout Storage located in the span when recording new attributes. This is likely a bug");
+                None
+            };
+            let diff = update && self.record_diff;
+            // The live attributes have to be gathered before taking the write lock below
+            let live = diff
+                .then(|| self.live_attributes(Some(span.scope()), None))
+                .flatten();
+            let changes = {
+                let mut extensions = span.extensions_mut();
+                if let Some(visitor) = extensions.get_mut::<SproutStorage>() {
+                    // The inherited value counts as the value before, as that's what was written
+                    let written = live.as_ref().unwrap_or(&visitor.attributes);
+                    let before = recorded.as_ref().filter(|_| diff).map(|recorded| {
+                        recorded
+                            .entries()
+                            .map(|(key, _)| (key.to_string(), written[key].clone()))
+                            .collect::<Vec<_>>()
+                    });
+                    values.record(visitor);
+                    before.map(|before| {
+                        let mut changes = JsonValue::new_object();
+                        for (key, before) in before {
+                            let after = visitor.attributes[key.as_str()].clone();
+                            insert_lossy(
+                                &mut changes,
+                                &key,
+                                object! { BEFORE => before, AFTER => after },
+                            );
+                        }
+                        changes
+                    })
+                } else {
+                    tracing::error!(target: "sprout", "Expected to find Sprout Storage located in the span when recording new attributes. This is likely a bug");
+                    return;
+                }
+            };
+            if let (true, Some(recorded)) = (update, recorded) {
+                // Only the newly recorded fields are written, rather than all of the attributes
+                let mut base = self.static_fields.clone();
+                for (key, value) in recorded.entries() {
+                    insert_lossy(&mut base, key, value.clone());
+                }
+                let record =
+                    self.build_span_record(&span, Type::Record, Some(base), |_, attributes, _| {
+                        if let Some(changes) = changes {
+                            insert_lossy(attributes, CHANGES, changes);
+                        }
+                    });
+                if let Some(record) = record {
+                    self.emit_built_span_record(&span, Type::Record, record);
+                }
+            }
+            let error_field = span.metadata().fields().field(ERROR);
+            if error_field.is_some_and(|field| values.contains(&field)) {
+                let errored = span
+                    .extensions()
+                    .get::<SproutStorage>()
+                    .is_some_and(|visitor| is_error(&visitor.fields[ERROR]));
+                if errored {
+                    self.mark_errored(Some(span.scope()));
+                }
             }
         } else {
             tracing::error!(target: "sprout","Expected to find Span ID when recording span attributes. This is likely a bug");
@@ -121,11 +1115,17 @@ where
 
     fn on_enter(&self, span: &Id, ctx: Context<'_, S>) {
         if let Some(span) = ctx.span(span) {
-            let mut extensions = span.extensions_mut();
-            if let Some(visitor) = extensions.get_mut::<SproutStorage>() {
-                visitor.entered_at = Some(Instant::now());
-            } else {
-                tracing::error!(target: "sprout", "Expected to find Sprout Storage located in the span when entering it. This is likely a bug");
+            {
+                let mut extensions = span.extensions_mut();
+                if let Some(visitor) = extensions.get_mut::<SproutStorage>() {
+                    visitor.timings.enter();
+                } else {
+                    tracing::error!(target: "sprout", "Expected to find Sprout Storage located in the span when entering it. This is likely a bug");
+                    return;
+                }
+            }
+            if self.span_events.contains(SpanEvents::ENTER) {
+                self.emit_span_record(&span, Type::Enter, |_, _, _| ());
             }
         } else {
             tracing::error!(target: "sprout",
@@ -134,18 +1134,95 @@ where
         }
     }
 
+    fn on_exit(&self, span: &Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(span) {
+            if let Some(visitor) = span.extensions_mut().get_mut::<SproutStorage>() {
+                visitor.timings.exit();
+            }
+            if self.span_events.contains(SpanEvents::EXIT) {
+                self.emit_span_record(&span, Type::Exit, |visitor, attributes, _| {
+                    if let Some(entered_at) = visitor.timings.entered_at {
+                        self.insert_duration(attributes, ELAPSED, entered_at.elapsed());
+                    }
+                });
+            }
+        } else {
+            tracing::error!(target: "sprout",
+                "Expected to find Span ID when exiting span. This is likely a bug"
+            );
+        }
+    }
+
     fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
-        let mut visitor = ctx
-            .lookup_current()
-            .and_then(|span| span.extensions_mut().get_mut::<SproutStorage>().cloned())
-            .unwrap_or_else(|| SproutStorage::new(&self.name, &self.version));
+        #[cfg(feature = "metrics")]
+        if let Some(metrics) = &self.metrics {
+            metrics.record_event(event.metadata());
+        }
+        // An event can name its parent rather than being within the current span, everything about
+        // it comes from that one span
+        let span = ctx.event_span(event).or_else(|| ctx.lookup_current());
+        let scope = || span.as_ref().map(SpanRef::scope);
+        // Cloning the storage resets the timings, so we need to take the ones we want first
+        let (mut visitor, timings) = span
+            .as_ref()
+            .and_then(|span| {
+                span.extensions().get::<SproutStorage>().map(|storage| {
+                    let timings = (storage.timings.entered_at, storage.timings.created_at);
+                    (storage.clone(), Some(timings))
+                })
+            })
+            .unwrap_or_else(|| (self.root_storage(), None));
 
+        // Fields pushed on this thread sit between those of the span and those of the event
+        for_each_field(|key, value| visitor.record_field(key, value.clone()));
         event.record(&mut visitor);
-        let elapsed: Option<u64> = visitor.entered_at.map(|t| t.elapsed().as_millis() as u64);
-        // It would be nice for it to have this value, but if it fails, it fails
-        visitor.add_attribute_opt(TIME_SINCE_START, elapsed).ok();
         let metadata = event.metadata();
-        if let Ok(bytes) = serialize_span(visitor.attributes, metadata, Type::Event) {
+        self.merge_into_canonical(scope(), &visitor.fields);
+        if self.event_counts || self.canonical_log_line {
+            let message = visitor.fields["message"]
+                .as_str()
+                .unwrap_or_else(|| metadata.target());
+            // Every span the event happened within counts it, not just the closest one
+            for span in scope().into_iter().flatten() {
+                if let Some(storage) = span.extensions_mut().get_mut::<SproutStorage>() {
+                    storage.event_counts.record(metadata.level(), message);
+                }
+            }
+        }
+        if *metadata.level() == Level::ERROR || is_error(&visitor.fields[ERROR]) {
+            self.mark_errored(scope());
+        }
+        if !self.event_records {
+            return;
+        }
+        let mut attributes = self
+            .live_attributes(scope(), Some(&visitor.fields))
+            .unwrap_or_else(|| visitor.clone_attributes());
+        if let Some((entered_at, created_at)) = timings {
+            if let Some(entered_at) = entered_at {
+                self.insert_duration(&mut attributes, TIME_SINCE_START, entered_at.elapsed());
+            }
+            self.insert_duration(&mut attributes, TIME_SINCE_CREATED, created_at.elapsed());
+        }
+        if let Some(root_created_at) = visitor.root_created_at {
+            self.insert_duration(&mut attributes, TIME_SINCE_ROOT, root_created_at.elapsed());
+        }
+        self.insert_ids(&mut attributes, &visitor);
+        self.insert_os_thread_id(&mut attributes);
+        self.providers.provide(Type::Event, &mut attributes);
+        self.insert_span_list(&mut attributes, scope());
+        if self.tree_documents {
+            if let Some(span) = &span {
+                if let Ok(record) = build_span(attributes, metadata, metadata.level(), Type::Event)
+                {
+                    if let Some(storage) = span.extensions_mut().get_mut::<SproutStorage>() {
+                        storage.tree.events.push(record);
+                    }
+                }
+                return;
+            }
+        }
+        if let Ok(bytes) = serialize_span(attributes, metadata, metadata.level(), Type::Event) {
             let _ = self.emit(bytes);
         } else {
             tracing::error!(target: "sprout",
@@ -156,20 +1233,66 @@ where
 
     fn on_close(&self, id: Id, ctx: Context<'_, S>) {
         if let Some(span) = ctx.span(&id) {
-            let mut extensions = span.extensions_mut();
-            if let Some(visitor) = extensions.get_mut::<SproutStorage>() {
-                let elapsed: Option<u64> = visitor
-                    .entered_at
-                    .take()
-                    .map(|t| t.elapsed().as_millis() as u64);
-                // It would be nice for it to have this value, but if it fails, it fails
-                visitor.add_attribute_opt(ELAPSED_MILLIS, elapsed).ok();
-                if let Ok(serialized) =
-                    serialize_span(visitor.clone_attributes(), span.metadata(), Type::Exit)
-                {
-                    let _ = self.emit(serialized);
+            let metadata = span.metadata();
+            let lifetime = span.extensions().get::<SproutStorage>().map(|storage| {
+                let lifetime = storage.timings.lifetime();
+                #[cfg(feature = "metrics")]
+                if let Some(metrics) = &self.metrics {
+                    metrics.record_span(metadata, &storage.attributes, lifetime);
                 }
+                lifetime
+            });
+            let exceeded = self
+                .slow_spans
+                .as_ref()
+                .zip(lifetime)
+                .and_then(|(slow_spans, lifetime)| slow_spans.exceeded(metadata, lifetime));
+            if let (Some(span_stats), Some(lifetime)) = (&self.span_stats, lifetime) {
+                let key = match span_stats.key() {
+                    StatsKey::Name => span.name().to_string(),
+                    StatsKey::Path => span
+                        .scope()
+                        .from_root()
+                        .map(|span| span.name())
+                        .collect::<Vec<_>>()
+                        .join(SPAN_PATH_SEPARATOR),
+                };
+                span_stats.record(key, lifetime);
+            }
+            let insert_summary = |visitor: &mut SproutStorage,
+                                  attributes: &mut JsonValue,
+                                  level: &mut Level| {
+                self.insert_close_fields(visitor, attributes, level, exceeded, self.event_counts)
             };
+            let separate_record = self.slow_spans.as_ref().map(SlowSpans::separate_record);
+            if exceeded.is_some() && separate_record == Some(true) {
+                self.emit_span_record(&span, Type::Slow, insert_summary);
+            }
+            if self.tree_documents {
+                // The close record is the span's node within the tree, so it's always needed
+                if let Some(node) = self.build_span_record(&span, Type::Close, None, insert_summary)
+                {
+                    self.close_tree_node(&span, node);
+                }
+            } else if self.span_events.contains(SpanEvents::CLOSE) {
+                self.emit_span_record(&span, Type::Close, insert_summary);
+            }
+            let canonical = span
+                .extensions()
+                .get::<SproutStorage>()
+                .is_some_and(|storage| storage.canonical.is_some());
+            if canonical {
+                self.emit_span_record(&span, Type::Canonical, |visitor, attributes, level| {
+                    if let Some(canonical) = visitor.canonical.take() {
+                        for (key, value) in canonical.entries() {
+                            insert_lossy(attributes, key, value.clone());
+                        }
+                    }
+                    // Any error within the request makes the whole line an error
+                    visitor.errored |= visitor.event_counts.error > 0;
+                    self.insert_close_fields(visitor, attributes, level, exceeded, true);
+                });
+            }
         } else {
             tracing::error!(target: "sprout",
                 "Expected to find Span ID when closing span. This is likely a bug"
@@ -0,0 +1,51 @@
+use json::JsonValue;
+use tracing::Id;
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// The next process-unique span id to hand out, `0` is never used so it can't be confused
+/// with an invalid id downstream
+static NEXT_SPAN_ID: AtomicU64 = AtomicU64::new(1);
+
+/// How the `span_id`, `parent_span_id` and `root_span_id` fields are written on each record
+///
+/// The `Id`s handed out by the tracing registry are only unique amongst the spans that are
+/// currently open, once a span closes its `Id` can be reused. If you're joining records across a
+/// long period of time you likely want one of the process-unique formats instead.
+///
+/// When trace ids are enabled every span is given a random id instead, as they are propagated to
+/// other services, see [`TrunkLayer::with_trace_ids`](crate::TrunkLayer::with_trace_ids). Those
+/// _(and the ids of spans tracked by OpenTelemetry)_ are always written as hex, as a random 64-bit
+/// number loses precision in JavaScript based tools. Only [`SpanIdFormat::Disabled`] still
+/// applies to them.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum SpanIdFormat {
+    /// Don't write any span ids on the records
+    Disabled,
+    /// The `Id` assigned by the tracing registry, written as a number
+    #[default]
+    Tracing,
+    /// A process-unique 64-bit value, written as a number
+    Unique,
+    /// A process-unique 64-bit value, written as a 16 character lowercase hex string
+    Hex,
+}
+
+impl SpanIdFormat {
+    /// Generates the id that will be stored against a newly created span
+    pub(crate) fn assign(&self, id: &Id) -> u64 {
+        match *self {
+            Self::Disabled | Self::Tracing => id.into_u64(),
+            Self::Unique | Self::Hex => NEXT_SPAN_ID.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+
+    /// Writes the id, `random` ids are always written as hex
+    pub(crate) fn render(&self, id: u64, random: bool) -> JsonValue {
+        match *self {
+            _ if random => format!("{:016x}", id).into(),
+            Self::Disabled | Self::Tracing | Self::Unique => id.into(),
+            Self::Hex => format!("{:016x}", id).into(),
+        }
+    }
+}
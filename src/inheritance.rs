@@ -0,0 +1,303 @@
+use json::JsonValue;
+
+use std::collections::HashMap;
+
+use crate::constants::PARENT_PREFIX;
+
+/// How far down the span tree a field is carried.
+///
+/// A child span or an event each count as one level below the span the field was recorded on, a
+/// field is always written on the records of that span itself.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum Inherit {
+    /// The field is inherited by every descendant and event
+    #[default]
+    Always,
+    /// The field is only written on the records of the span it was recorded on
+    Never,
+    /// The field is inherited this many levels down, `Levels(1)` reaches the span's own events
+    /// and its children, but not their events or children
+    Levels(usize),
+}
+
+/// What happens when a span or event records a field it has already inherited.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum Collision {
+    /// The newly recorded value replaces the inherited one
+    #[default]
+    ChildWins,
+    /// The inherited value is kept, the newly recorded one is only written in the span list
+    /// _(see [`TrunkLayer::with_span_list`](crate::TrunkLayer::with_span_list))_
+    ParentWins,
+    /// The newly recorded value replaces the inherited one, which is kept under the same key
+    /// prefixed with `parent.` _(i.e. `id` & `parent.id`)_ and inherited as far as the field is
+    KeepBoth,
+}
+
+/// Policies for which fields are inherited by descendant spans & events, and what happens when
+/// they collide.
+///
+/// By default every field is inherited by every descendant and the child wins when there's a
+/// collision. A policy can be set for a specific field, or for every field starting with a prefix
+/// _(the field name wins over a prefix, and the longest matching prefix wins over the rest)_.
+/// The `name` & `version` of the application aren't recorded on any span, so they are always
+/// inherited.
+///
+/// # Examples
+///
+/// ```
+/// use tracing_sprout::{Collision, Inherit, Inheritance};
+///
+/// let inheritance = Inheritance::new()
+///     .with_prefix("_", Inherit::Never)
+///     .with_field("request_body", Inherit::Levels(1))
+///     .with_field_collision("id", Collision::KeepBoth);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Inheritance {
+    inherit: Rules<Inherit>,
+    collision: Rules<Collision>,
+}
+
+impl Inheritance {
+    /// Creates the default policies, on their own these behave just like not having any
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The policy for any field that doesn't have a more specific one
+    pub fn with_default(mut self, inherit: Inherit) -> Self {
+        self.inherit.default = inherit;
+        self
+    }
+
+    /// The policy for the field with the given name
+    pub fn with_field(mut self, name: impl Into<String>, inherit: Inherit) -> Self {
+        self.inherit.fields.insert(name.into(), inherit);
+        self
+    }
+
+    /// The policy for every field whose name starts with the given prefix
+    pub fn with_prefix(mut self, prefix: impl Into<String>, inherit: Inherit) -> Self {
+        self.inherit.prefixes.push((prefix.into(), inherit));
+        self
+    }
+
+    /// What happens on a collision for any field that doesn't have a more specific policy
+    pub fn with_collision(mut self, collision: Collision) -> Self {
+        self.collision.default = collision;
+        self
+    }
+
+    /// What happens on a collision of the field with the given name
+    pub fn with_field_collision(mut self, name: impl Into<String>, collision: Collision) -> Self {
+        self.collision.fields.insert(name.into(), collision);
+        self
+    }
+
+    /// What happens on a collision of every field whose name starts with the given prefix
+    pub fn with_prefix_collision(
+        mut self,
+        prefix: impl Into<String>,
+        collision: Collision,
+    ) -> Self {
+        self.collision.prefixes.push((prefix.into(), collision));
+        self
+    }
+
+    /// Moves the attributes one level down the tree, dropping the ones that shouldn't be
+    /// inherited any further.
+    ///
+    /// `depths` holds how many levels each recorded field is from the span it was recorded on,
+    /// fields that aren't in there weren't recorded on a span and are always inherited.
+    pub(crate) fn descend(&self, attributes: &mut JsonValue, depths: &mut HashMap<String, usize>) {
+        depths.retain(|key, depth| {
+            *depth += 1;
+            // The parent's value kept on a collision goes as far as the field itself would have
+            let field = key.strip_prefix(PARENT_PREFIX).unwrap_or(key);
+            let keep = match self.inherit.get(field) {
+                Inherit::Always => true,
+                Inherit::Never => false,
+                Inherit::Levels(levels) => *depth <= levels,
+            };
+            if !keep {
+                attributes.remove(key);
+            }
+            keep
+        });
+    }
+
+    /// Records a field against the merged attributes, resolving any collision with an inherited
+    /// value
+    pub(crate) fn insert(
+        &self,
+        attributes: &mut JsonValue,
+        depths: &mut HashMap<String, usize>,
+        key: &str,
+        value: JsonValue,
+    ) -> json::Result<()> {
+        let inherited = attributes.has_key(key) && depths.get(key) != Some(&0);
+        if inherited {
+            match self.collision.get(key) {
+                Collision::ChildWins => (),
+                Collision::ParentWins => return Ok(()),
+                Collision::KeepBoth => {
+                    let parent_key = format!("{}{}", PARENT_PREFIX, key);
+                    let parent = attributes.remove(key);
+                    attributes.insert(&parent_key, parent)?;
+                    if let Some(depth) = depths.get(key).copied() {
+                        depths.insert(parent_key, depth);
+                    }
+                }
+            }
+        }
+        attributes.insert(key, value)?;
+        depths.insert(key.to_string(), 0);
+        Ok(())
+    }
+}
+
+/// A value per field name or prefix, with a default for the rest
+#[derive(Debug, Clone, Default)]
+struct Rules<T> {
+    default: T,
+    fields: HashMap<String, T>,
+    prefixes: Vec<(String, T)>,
+}
+
+impl<T: Copy> Rules<T> {
+    fn get(&self, key: &str) -> T {
+        if let Some(value) = self.fields.get(key) {
+            return *value;
+        }
+        self.prefixes
+            .iter()
+            .filter(|(prefix, _)| key.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, value)| *value)
+            .unwrap_or(self.default)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use json::object;
+
+    /// Records each field on a root span, returning its attributes & depths
+    fn record(inheritance: &Inheritance, fields: JsonValue) -> (JsonValue, HashMap<String, usize>) {
+        let mut attributes = object! { "name": "test" };
+        let mut depths = HashMap::new();
+        for (key, value) in fields.entries() {
+            inheritance
+                .insert(&mut attributes, &mut depths, key, value.clone())
+                .unwrap();
+        }
+        (attributes, depths)
+    }
+
+    #[test]
+    fn levels() {
+        let inheritance = Inheritance::new()
+            .with_field("once", Inherit::Levels(1))
+            .with_field("twice", Inherit::Levels(2))
+            .with_prefix("_", Inherit::Never);
+        let (mut attributes, mut depths) = record(
+            &inheritance,
+            object! { "once": 1, "twice": 2, "_private": 3, "always": 4 },
+        );
+
+        inheritance.descend(&mut attributes, &mut depths);
+        assert_eq!(
+            attributes,
+            object! { "name": "test", "once": 1, "twice": 2, "always": 4 }
+        );
+        inheritance.descend(&mut attributes, &mut depths);
+        assert_eq!(
+            attributes,
+            object! { "name": "test", "twice": 2, "always": 4 }
+        );
+        inheritance.descend(&mut attributes, &mut depths);
+        assert_eq!(attributes, object! { "name": "test", "always": 4 });
+        assert_eq!(depths.get("always"), Some(&3));
+        assert!(!depths.contains_key("twice"));
+    }
+
+    #[test]
+    fn recording_again_resets_the_depth() {
+        let inheritance = Inheritance::new().with_field("once", Inherit::Levels(1));
+        let (mut attributes, mut depths) = record(&inheritance, object! { "once": 1 });
+        inheritance.descend(&mut attributes, &mut depths);
+        inheritance
+            .insert(&mut attributes, &mut depths, "once", 2.into())
+            .unwrap();
+        inheritance.descend(&mut attributes, &mut depths);
+        assert_eq!(attributes["once"], 2);
+    }
+
+    #[test]
+    fn child_wins() {
+        let inheritance = Inheritance::new();
+        let (mut attributes, mut depths) = record(&inheritance, object! { "id": 1 });
+        inheritance.descend(&mut attributes, &mut depths);
+        inheritance
+            .insert(&mut attributes, &mut depths, "id", 2.into())
+            .unwrap();
+        assert_eq!(attributes, object! { "name": "test", "id": 2 });
+    }
+
+    #[test]
+    fn parent_wins() {
+        let inheritance = Inheritance::new().with_field_collision("id", Collision::ParentWins);
+        let (mut attributes, mut depths) = record(&inheritance, object! { "id": 1 });
+        // Only an inherited value wins, the span can still change its own
+        inheritance
+            .insert(&mut attributes, &mut depths, "id", 2.into())
+            .unwrap();
+        assert_eq!(attributes["id"], 2);
+
+        inheritance.descend(&mut attributes, &mut depths);
+        inheritance
+            .insert(&mut attributes, &mut depths, "id", 3.into())
+            .unwrap();
+        assert_eq!(attributes, object! { "name": "test", "id": 2 });
+        assert_eq!(depths.get("id"), Some(&1));
+    }
+
+    #[test]
+    fn keep_both() {
+        let inheritance = Inheritance::new()
+            .with_prefix_collision("req", Collision::KeepBoth)
+            .with_field("request_id", Inherit::Levels(2));
+        let (mut attributes, mut depths) = record(&inheritance, object! { "request_id": 1 });
+        inheritance.descend(&mut attributes, &mut depths);
+        inheritance
+            .insert(&mut attributes, &mut depths, "request_id", 2.into())
+            .unwrap();
+        assert_eq!(
+            attributes,
+            object! { "name": "test", "parent.request_id": 1, "request_id": 2 }
+        );
+        assert_eq!(depths.get("parent.request_id"), Some(&1));
+        assert_eq!(depths.get("request_id"), Some(&0));
+
+        // The parent's value keeps the depth it was inherited at, rather than starting over
+        inheritance.descend(&mut attributes, &mut depths);
+        assert_eq!(attributes["parent.request_id"], 1);
+        inheritance.descend(&mut attributes, &mut depths);
+        assert!(attributes["parent.request_id"].is_null());
+    }
+
+    #[test]
+    fn fields_win_over_prefixes() {
+        let inheritance = Inheritance::new()
+            .with_default(Inherit::Never)
+            .with_prefix("http", Inherit::Levels(1))
+            .with_prefix("http.request", Inherit::Always)
+            .with_field("http.request.body", Inherit::Never);
+        assert_eq!(inheritance.inherit.get("user_id"), Inherit::Never);
+        assert_eq!(inheritance.inherit.get("http.route"), Inherit::Levels(1));
+        assert_eq!(inheritance.inherit.get("http.request.id"), Inherit::Always);
+        assert_eq!(inheritance.inherit.get("http.request.body"), Inherit::Never);
+    }
+}
@@ -7,13 +7,62 @@
 //!
 //! ## Features
 //! - All traces will receive their parent's attributes as well as their own, child attributes will
-//! take precedence if there are collisions
-//! - There is a very minimal timing capability that adds elapsed time to `EVENT` and `EXIT` traces
+//!   take precedence if there are collisions
+//! - Which attributes are inherited, how far down the tree, and what happens when a child
+//!   collides with its parent is configurable per field or prefix, see [`Inheritance`]
+//! - Optionally, inheritance can be live, so values recorded on a span after its children were
+//!   created still show up in their records, see [`TrunkLayer::with_live_inheritance`]
+//! - Fields can be added to or removed from the current span at runtime, without declaring them
+//!   when it's created, and the merged attributes of any span can be read back, see
+//!   [`current_span`]
+//! - Code paths without spans can still add contextual fields to their events with
+//!   [`with_fields`] or [`push_fields`], which are kept for the current thread
+//! - The context of a span can be captured and re-applied on another thread, or passed to a
+//!   child process through environment variables, see [`SproutContext`]
+//! - Static fields _(environment, region, git sha)_ and fields describing the process _(pid,
+//!   hostname, executable name & OS thread id)_ can be added to every record, along with the
+//!   container id, Kubernetes pod & cgroup limits when running in a container, see
+//!   [`TrunkLayer::with_static_field`] & [`ProcessFields`]
+//! - Fields that change from one record to the next can be added as each record is written by
+//!   registering a [`FieldProvider`]
+//! - There is a very minimal timing capability that adds elapsed time to `EVENT`, `EXIT` and `CLOSE` traces.
+//!   `CLOSE` traces report the span's total lifetime, as well as the time it spent busy _(summed across
+//!   every enter/exit)_ & idle and how many times it was entered, which is far more meaningful for
+//!   async spans. Every `EVENT` also carries the time since its span was entered & started, and
+//!   since the root span _(i.e. the request)_ started, placing it on the overall timeline
+//! - Spans that take longer than a configured threshold _(globally, per span name or per target)_
+//!   are flagged as `slow` and raised to `WARN`, see [`SlowSpans`]
+//! - `CLOSE` traces can report how many events happened within the span _(by level, including its
+//!   descendants)_ and the first error message, see [`TrunkLayer::with_event_counts`]
+//! - Spans _(and optionally their ancestors)_ that an error was recorded within can be marked as
+//!   failed, raising their `CLOSE` trace to `ERROR`, see [`ErrorPropagation`]
+//! - A [canonical log line](https://stripe.com/blog/canonical-log-lines) can be written for every
+//!   root span, merging every field recorded within it into one wide record _(see
+//!   [`TrunkLayer::with_canonical_log_line`])_
+//! - For offline debugging, each root span can be written as one nested JSON document once it
+//!   closes, see [`TrunkLayer::with_tree_documents`]
+//! - Rough latency statistics _(count, p50/p90/p99, max)_ per span can be aggregated in-process and
+//!   written as periodic `summary` records, see [`SpanStats`]
+//! - Which points of a span's lifecycle _(new, enter, exit, record & close)_ produce a record is
+//!   configurable, see [`SpanEvents`]. `record` records hold only the newly recorded fields, and
+//!   optionally what they were before, see [`TrunkLayer::with_record_diff`]
 //! - `TRACE`, `DEBUG` and `ERROR` logs get slighly more metadata (file name, line number, module path & target) attached
-//! to them
+//!   to them
+//! - Every record emitted from within a span carries a `span_id`, `parent_span_id` & `root_span_id`
+//!   so that records can be correlated downstream _(see [`SpanIdFormat`])_
+//! - Optionally, root spans can generate a 128-bit trace id _(or continue one from an incoming W3C
+//!   `traceparent`)_ which is inherited by every descendant, see [`TrunkLayer::with_trace_ids`] and
+//!   the [`propagation`] module
+//! - With the `opentelemetry` feature, spans tracked by
+//!   [tracing-opentelemetry](https://docs.rs/tracing-opentelemetry) use the OpenTelemetry
+//!   `trace_id`, `span_id` & `trace_flags` so that records line up with the exported traces
+//! - With the `metrics` feature, span durations and event counts are recorded through the
+//!   [metrics](https://docs.rs/metrics) facade, see [`TrunkLayer::with_metrics`]
+//! - Optionally, every record can carry the full span stack it was emitted from _(see
+//!   [`TrunkLayer::with_span_list`])_
 //! - Avoids panics - as much as possible it opts to handle failure by `eprintln`ing to `stdout`.
-//! These scenarios should be few and far between, but it's better that a failure in your tracing
-//! implementation doesn't poison your main application.
+//!   These scenarios should be few and far between, but it's better that a failure in your tracing
+//!   implementation doesn't poison your main application.
 //!
 //! ## Example
 //!
@@ -24,7 +73,11 @@
 //! use tracing_subscriber::{EnvFilter, Registry};
 //!
 //! let env_filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
-//! let formatting_layer = TrunkLayer::new("My Application".to_string(), std::io::stdout);
+//! let formatting_layer = TrunkLayer::new(
+//!     "My Application".to_string(),
+//!     env!("CARGO_PKG_VERSION").to_string(),
+//!     std::io::stdout,
+//! );
 //! let subscriber = Registry::default()
 //!      .with(env_filter)
 //!      .with(formatting_layer);
@@ -49,9 +102,9 @@
 //! See the [examples](https://github.com/naamancurtis/tracing-sprout/tree/main/examples) for a basic demonstration of how this can be used. The **basic.rs** example in there would output logs like the following:
 //!
 //! ```txt
-//! {"app_name":"I'm Groot","pid":2735,"id":"1","time":"Sat, 13 Feb 2021 14:16:15 +0000","timestamp":1613225775,"msg":"[EPIC MONTAGE | START]","level":"info","span.type":"enter"}
-//! {"app_name":"I'm Groot","pid":2735,"id":"1","group":"[\"Peter Quill\", \"Gamora\", \"Drax\", \"Rocket\"]","time":"Sat, 13 Feb 2021 14:16:15 +0000","timestamp":1613225775,"msg":"[EVENT] Trying to plug in the power","level":"trace","file":"examples/basic.rs","line":32,"module":"basic","target":"basic","thread_id":"ThreadId(1)","thread_name":"main","span.type":"event"}
-//! {"app_name":"I'm Groot","pid":2735,"id":"2","info":"I'm overwriting my parents ID","time":"Sat, 13 Feb 2021 14:16:15 +0000","timestamp":1613225775,"msg":"[MUSIC IS PLAYING | START]","level":"debug","file":"examples/basic.rs","line":34,"module":"basic","target":"basic","thread_id":"ThreadId(1)","thread_name":"main","span.type":"enter"}
+//! {"name":"I'm Groot","version":"0.1.0-alpha.6","pid":2735,"id":"1","span_id":1,"root_span_id":1,"time":"Sun, 18 Oct 2026 09:33:59 +0000","msg":"[EPIC MONTAGE | START]","level":"info","target":"basic","span_type":"new"}
+//! {"name":"I'm Groot","version":"0.1.0-alpha.6","pid":2735,"id":"1","group":["Peter Quill","Gamora","Drax","Rocket"],"time_since_span_entered_ms":0,"time_since_span_started_ms":0,"time_since_root_span_started_ms":0,"span_id":1,"root_span_id":1,"time":"Sun, 18 Oct 2026 09:33:59 +0000","msg":"[EVENT] Trying to plug in the power","level":"trace","target":"basic","thread_id":"ThreadId(1)","thread_name":"main","span_type":"event"}
+//! {"name":"I'm Groot","version":"0.1.0-alpha.6","pid":2735,"id":"2","info":"I'm overwriting my parents ID","span_id":2,"parent_span_id":1,"root_span_id":1,"time":"Sun, 18 Oct 2026 09:33:59 +0000","msg":"[MUSIC IS PLAYING | START]","level":"debug","target":"basic","thread_id":"ThreadId(1)","thread_name":"main","span_type":"new"}
 //! ...
 //! ```
 //!
@@ -59,12 +112,14 @@
 //! _(pino-pretty)_ you get the following output
 //!
 //! ```txt
-//! [Sat, 13 Feb 2021 14:14:54 +0000] INFO (1331): [EPIC MONTAGE | START]
-//!     app_name: "I'm Groot"
+//! [Sat, 13 Feb 2021 14:14:54 +0000] INFO (I'm Groot): [EPIC MONTAGE | START]
+//!     version: "0.1.0-alpha.6"
+//!     pid: 2735
 //!     id: "1"
-//!     span.type: "enter"
-//! [Sat, 13 Feb 2021 14:14:54 +0000] TRACE (1331): [EVENT] Trying to plug in the power
-//!     app_name: "I'm Groot"
+//!     span_type: "new"
+//! [Sat, 13 Feb 2021 14:14:54 +0000] TRACE (I'm Groot): [EVENT] Trying to plug in the power
+//!     version: "0.1.0-alpha.6"
+//!     pid: 2735
 //!     id: "1"
 //!     group: "[\"Peter Quill\", \"Gamora\", \"Drax\", \"Rocket\"]"
 //!     file: "examples/basic.rs"
@@ -73,9 +128,10 @@
 //!     target: "basic"
 //!     thread_id: "ThreadId(1)"
 //!     thread_name: "main"
-//!     span.type: "event"
-//! [Sat, 13 Feb 2021 14:14:54 +0000] DEBUG (1331): [MUSIC IS PLAYING | START]
-//!     app_name: "I'm Groot"
+//!     span_type: "event"
+//! [Sat, 13 Feb 2021 14:14:54 +0000] DEBUG (I'm Groot): [MUSIC IS PLAYING | START]
+//!     version: "0.1.0-alpha.6"
+//!     pid: 2735
 //!     id: "2"
 //!     info: "I'm overwriting my parents ID"
 //!     file: "examples/basic.rs"
@@ -84,17 +140,48 @@
 //!     target: "basic"
 //!     thread_id: "ThreadId(1)"
 //!     thread_name: "main"
-//!     span.type: "enter"
+//!     span_type: "new"
 //!     ...
 //! ```
 
 pub(crate) mod constants;
+mod container;
+mod context;
+pub mod current_span;
+mod duration;
 mod error;
+mod error_propagation;
 mod formatting;
+mod ids;
+mod inheritance;
+#[cfg(feature = "metrics")]
+mod metrics_bridge;
+#[cfg(feature = "opentelemetry")]
+mod otel;
+mod process;
+pub mod propagation;
+mod providers;
+mod scoped_fields;
+mod slow;
+mod span_events;
+mod stats;
 mod storage;
 pub(crate) mod util;
 
 pub(crate) use error::SproutError;
 pub(crate) type Result<T> = std::result::Result<T, SproutError>;
 
+pub use context::{ContextGuard, SproutContext};
+pub use duration::DurationUnit;
+pub use error_propagation::ErrorPropagation;
 pub use formatting::TrunkLayer;
+pub use ids::SpanIdFormat;
+pub use inheritance::{Collision, Inherit, Inheritance};
+pub use json::JsonValue;
+pub use process::ProcessFields;
+pub use propagation::TraceContext;
+pub use providers::{FieldProvider, RecordTypes};
+pub use scoped_fields::{push_fields, with_fields, FieldsGuard};
+pub use slow::SlowSpans;
+pub use span_events::SpanEvents;
+pub use stats::{SpanStats, SpanStatsHandle, SpanStatsSnapshot, StatsKey};
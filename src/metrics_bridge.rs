@@ -0,0 +1,60 @@
+//! Bridges span durations and event counts into the [`metrics`](https://docs.rs/metrics) crate
+//! facade, so whichever recorder the application installs gets them too.
+use json::JsonValue;
+use metrics::Label;
+use tracing_core::metadata::Metadata;
+
+use std::time::Duration;
+
+use crate::util::level_as_str;
+
+/// The histogram recorded, in seconds, every time a span closes
+pub(crate) const SPAN_DURATION: &str = "span.duration";
+/// The counter incremented for every event
+pub(crate) const EVENTS: &str = "events";
+
+pub(crate) const LABEL_SPAN: &str = "span";
+pub(crate) const LABEL_LEVEL: &str = "level";
+pub(crate) const LABEL_TARGET: &str = "target";
+
+/// Which of the span's inherited attributes are used as labels on the duration histogram
+#[derive(Debug, Clone, Default)]
+pub(crate) struct MetricsBridge {
+    labels: Vec<String>,
+}
+
+impl MetricsBridge {
+    pub(crate) fn new(labels: Vec<String>) -> Self {
+        Self { labels }
+    }
+
+    pub(crate) fn record_span(
+        &self,
+        metadata: &Metadata,
+        attributes: &JsonValue,
+        elapsed: Duration,
+    ) {
+        let mut labels = Vec::with_capacity(self.labels.len() + 1);
+        labels.push(Label::new(LABEL_SPAN, metadata.name()));
+        for key in &self.labels {
+            let value = &attributes[key.as_str()];
+            if value.is_null() {
+                continue;
+            }
+            let value = match value.as_str() {
+                Some(value) => value.to_string(),
+                None => value.dump(),
+            };
+            labels.push(Label::new(key.clone(), value));
+        }
+        metrics::histogram!(SPAN_DURATION, labels).record(elapsed.as_secs_f64());
+    }
+
+    pub(crate) fn record_event(&self, metadata: &Metadata) {
+        let labels = [
+            (LABEL_LEVEL, level_as_str(metadata.level()).to_string()),
+            (LABEL_TARGET, metadata.target().to_string()),
+        ];
+        metrics::counter!(EVENTS, &labels).increment(1);
+    }
+}
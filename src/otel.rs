@@ -0,0 +1,51 @@
+//! Interop with [tracing-opentelemetry](https://docs.rs/tracing-opentelemetry), so that the ids
+//! written on sprout records match the ones on the exported OpenTelemetry spans.
+use opentelemetry::trace::{SamplingDecision, TraceContextExt};
+use tracing_opentelemetry::OtelData;
+use tracing_subscriber::registry::{LookupSpan, SpanRef};
+
+use crate::propagation::FLAG_SAMPLED;
+
+/// The OpenTelemetry span context that tracing-opentelemetry assigned to a span
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct OtelIds {
+    pub(crate) trace_id: u128,
+    pub(crate) span_id: u64,
+    pub(crate) trace_flags: u8,
+}
+
+/// Reads the span context that the `OpenTelemetryLayer` stored in the span's extensions.
+///
+/// The OpenTelemetry layer has to have seen the span first, so it needs to be added to the
+/// registry _before_ the `TrunkLayer`.
+pub(crate) fn otel_ids<'a, S>(span: &SpanRef<'a, S>) -> Option<OtelIds>
+where
+    S: LookupSpan<'a>,
+{
+    let extensions = span.extensions();
+    let data = extensions.get::<OtelData>()?;
+    let span_id = data.builder.span_id?;
+
+    let parent = data.parent_cx.span();
+    let parent = parent.span_context();
+    // A trace id is only assigned to the builder when there's no parent to inherit it from
+    let trace_id = match data.builder.trace_id {
+        Some(trace_id) => trace_id,
+        None if parent.is_valid() => parent.trace_id(),
+        None => return None,
+    };
+    // The sampler normally only runs once the span is built, so until then the best guess is the
+    // parent's decision
+    let trace_flags = match &data.builder.sampling_result {
+        Some(result) if result.decision == SamplingDecision::RecordAndSample => FLAG_SAMPLED,
+        Some(_) => 0,
+        None if parent.is_valid() => parent.trace_flags().to_u8(),
+        None => FLAG_SAMPLED,
+    };
+
+    Some(OtelIds {
+        trace_id: u128::from_be_bytes(trace_id.to_bytes()),
+        span_id: u64::from_be_bytes(span_id.to_bytes()),
+        trace_flags,
+    })
+}
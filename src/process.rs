@@ -0,0 +1,95 @@
+use json::JsonValue;
+
+use std::env;
+use std::fs;
+
+use crate::constants::{EXECUTABLE, HOSTNAME, PID};
+use crate::container::insert_container;
+use crate::util::{flag_ops, insert_lossy};
+
+/// Fields describing the process that can be added to every record, the values can be combined
+/// with `|`, for example `ProcessFields::PID | ProcessFields::HOSTNAME`.
+///
+/// Everything apart from [`ProcessFields::OS_THREAD_ID`] is looked up once when the layer is
+/// built. Any value that can't be found on the current platform is left out.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ProcessFields(u8);
+
+impl ProcessFields {
+    /// The id of the process, as `pid`
+    pub const PID: ProcessFields = ProcessFields(1);
+    /// The name of the host, as `hostname`
+    pub const HOSTNAME: ProcessFields = ProcessFields(1 << 1);
+    /// The file name of the executable, as `executable`
+    pub const EXECUTABLE: ProcessFields = ProcessFields(1 << 2);
+    /// The id the operating system gave the thread that wrote the record, as `os_thread_id`.
+    /// This is looked up for every record, and is currently only available on Linux.
+    pub const OS_THREAD_ID: ProcessFields = ProcessFields(1 << 3);
+    /// What can be found out about the container the process is running in - its `container_id`
+    /// _(from `/proc/self/cgroup` or `/proc/self/mountinfo`)_, the `pod_name`, `pod_namespace` &
+    /// `node_name` _(from the `POD_NAME`, `POD_NAMESPACE` & `NODE_NAME` environment variables,
+    /// set through the Kubernetes downward API)_ and the cgroup `cpu_limit` & `memory_limit_bytes`
+    pub const CONTAINER: ProcessFields = ProcessFields(1 << 4);
+    /// None of the process fields
+    pub const NONE: ProcessFields = ProcessFields(0);
+    /// All of the process fields, including [`ProcessFields::CONTAINER`]
+    pub const ALL: ProcessFields = ProcessFields(
+        Self::PID.0
+            | Self::HOSTNAME.0
+            | Self::EXECUTABLE.0
+            | Self::OS_THREAD_ID.0
+            | Self::CONTAINER.0,
+    );
+
+    /// Adds the fields that don't change for the lifetime of the process
+    pub(crate) fn insert_static(&self, attributes: &mut JsonValue) {
+        if self.contains(Self::PID) {
+            insert_lossy(attributes, PID, std::process::id());
+        }
+        if let Some(hostname) = hostname().filter(|_| self.contains(Self::HOSTNAME)) {
+            insert_lossy(attributes, HOSTNAME, hostname);
+        }
+        if let Some(executable) = executable().filter(|_| self.contains(Self::EXECUTABLE)) {
+            insert_lossy(attributes, EXECUTABLE, executable);
+        }
+        if self.contains(Self::CONTAINER) {
+            insert_container(attributes);
+        }
+    }
+}
+
+impl Default for ProcessFields {
+    fn default() -> Self {
+        Self::NONE
+    }
+}
+
+flag_ops!(ProcessFields);
+
+/// The id the operating system gave the current thread, it's looked up once per thread
+pub(crate) fn os_thread_id() -> Option<u64> {
+    thread_local! {
+        // `/proc/thread-self` links to `<pid>/task/<tid>`
+        static OS_THREAD_ID: Option<u64> = fs::read_link("/proc/thread-self")
+            .ok()
+            .and_then(|path| path.file_name()?.to_str()?.parse().ok());
+    }
+    OS_THREAD_ID.try_with(|id| *id).ok().flatten()
+}
+
+fn hostname() -> Option<String> {
+    env::var("HOSTNAME")
+        .ok()
+        .or_else(|| fs::read_to_string("/proc/sys/kernel/hostname").ok())
+        .or_else(|| fs::read_to_string("/etc/hostname").ok())
+        .or_else(|| env::var("COMPUTERNAME").ok())
+        .map(|hostname| hostname.trim().to_string())
+        .filter(|hostname| !hostname.is_empty())
+}
+
+fn executable() -> Option<String> {
+    let path = env::current_exe()
+        .ok()
+        .or_else(|| env::args_os().next().map(Into::into))?;
+    path.file_name()?.to_str().map(String::from)
+}
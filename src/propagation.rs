@@ -0,0 +1,338 @@
+//! Helpers for propagating a trace across service boundaries using the
+//! [W3C Trace Context](https://www.w3.org/TR/trace-context/) `traceparent` & `tracestate` headers
+//!
+//! To continue an incoming trace, record the incoming `traceparent` _(and optionally
+//! `tracestate`)_ as fields on your root span. When trace ids are enabled on the
+//! [`TrunkLayer`](crate::TrunkLayer) the root span will adopt the incoming trace id rather than
+//! generating a new one, and every descendant will inherit it. The two fields are removed once
+//! they've been adopted, with the caller's span id written as the root's `parent_span_id`.
+//!
+//! ```no_run
+//! use tracing::info_span;
+//! use tracing_sprout::TraceContext;
+//!
+//! # let incoming: Option<&str> = None;
+//! let span = info_span!("http_request", traceparent = incoming.unwrap_or_default());
+//! let _guard = span.enter();
+//!
+//! // Later on, when calling another service
+//! if let Some(context) = TraceContext::current() {
+//!     let header_value = context.traceparent();
+//! }
+//! ```
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::storage::with_current_storage;
+
+/// The `traceparent` version that we both understand and produce
+const VERSION: &str = "00";
+/// Whether the trace has been sampled by the caller, the only flag currently defined by the spec
+pub(crate) const FLAG_SAMPLED: u8 = 0x01;
+
+static RANDOM_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// The trace context of a span, as described by the W3C Trace Context specification
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceContext {
+    /// The 128-bit id shared by every span in the trace
+    pub trace_id: u128,
+    /// The 64-bit id of the span, in an incoming `traceparent` this is the caller's span
+    pub span_id: u64,
+    /// The trace flags, see [the spec](https://www.w3.org/TR/trace-context/#trace-flags)
+    pub trace_flags: u8,
+    /// The vendor specific `tracestate`, this is passed along untouched
+    pub trace_state: Option<String>,
+}
+
+impl TraceContext {
+    /// Parses the value of a `traceparent` header, returning `None` if it is invalid
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_sprout::TraceContext;
+    ///
+    /// let context =
+    ///     TraceContext::parse_traceparent("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01")
+    ///         .unwrap();
+    /// assert_eq!(context.trace_id, 0x4bf92f3577b34da6a3ce929d0e0e4736);
+    /// assert_eq!(context.span_id, 0x00f067aa0ba902b7);
+    /// ```
+    pub fn parse_traceparent(value: &str) -> Option<Self> {
+        let mut parts = value.trim().split('-');
+        let version = parts.next()?;
+        let trace_id = parts.next()?;
+        let span_id = parts.next()?;
+        let flags = parts.next()?;
+
+        // Future versions are allowed to append fields, but version `00` has exactly four
+        if version.len() != 2 || version == "ff" || (version == VERSION && parts.next().is_some()) {
+            return None;
+        }
+        parse_lower_hex(version)?;
+        if trace_id.len() != 32 || span_id.len() != 16 || flags.len() != 2 {
+            return None;
+        }
+        let trace_id =
+            parse_lower_hex(trace_id).and_then(|id| u128::from_str_radix(id, 16).ok())?;
+        let span_id = parse_lower_hex(span_id).and_then(|id| u64::from_str_radix(id, 16).ok())?;
+        let trace_flags = parse_lower_hex(flags).and_then(|f| u8::from_str_radix(f, 16).ok())?;
+        if trace_id == 0 || span_id == 0 {
+            return None;
+        }
+
+        Some(Self {
+            trace_id,
+            span_id,
+            trace_flags,
+            trace_state: None,
+        })
+    }
+
+    /// Sets the `tracestate` that accompanies this context
+    pub fn with_trace_state(mut self, trace_state: Option<String>) -> Self {
+        self.trace_state = trace_state.filter(|s| !s.trim().is_empty());
+        self
+    }
+
+    /// Formats this context as the value of a `traceparent` header
+    pub fn traceparent(&self) -> String {
+        self.to_string()
+    }
+
+    /// The trace id formatted as a 32 character lowercase hex string
+    pub fn trace_id_hex(&self) -> String {
+        format_trace_id(self.trace_id)
+    }
+
+    /// Gets the trace context of the current span, so that it can be passed to another service.
+    ///
+    /// This returns `None` if there is no current span, the global subscriber isn't built on top
+    /// of a `tracing_subscriber::Registry` or if trace ids aren't enabled on the layer.
+    pub fn current() -> Option<Self> {
+        with_current_storage(|storage| {
+            let trace_id = storage.trace_id?;
+            let span_id = storage.span_id?;
+            Some(Self {
+                trace_id,
+                span_id,
+                trace_flags: storage.trace_flags,
+                trace_state: storage.trace_state.clone(),
+            })
+        })
+        .flatten()
+    }
+}
+
+impl fmt::Display for TraceContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}-{:032x}-{:016x}-{:02x}",
+            VERSION, self.trace_id, self.span_id, self.trace_flags
+        )
+    }
+}
+
+/// Extracts the trace context from the `traceparent` & `tracestate` headers of an incoming
+/// request
+#[cfg(feature = "http")]
+pub fn extract(headers: &http::HeaderMap) -> Option<TraceContext> {
+    let traceparent = headers.get(TRACEPARENT_HEADER)?.to_str().ok()?;
+    let trace_state = headers
+        .get_all(TRACESTATE_HEADER)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .collect::<Vec<_>>()
+        .join(",");
+    TraceContext::parse_traceparent(traceparent).map(|c| c.with_trace_state(Some(trace_state)))
+}
+
+/// Injects the trace context of the current span into the headers of an outgoing request.
+///
+/// Returns `false` if there was no trace context to inject, see [`TraceContext::current`]
+#[cfg(feature = "http")]
+pub fn inject(headers: &mut http::HeaderMap) -> bool {
+    match TraceContext::current() {
+        Some(context) => {
+            inject_context(&context, headers);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Injects the given trace context into the headers of an outgoing request
+#[cfg(feature = "http")]
+pub fn inject_context(context: &TraceContext, headers: &mut http::HeaderMap) {
+    if let Ok(value) = http::HeaderValue::from_str(&context.traceparent()) {
+        headers.insert(TRACEPARENT_HEADER, value);
+    }
+    match context
+        .trace_state
+        .as_deref()
+        .and_then(|s| http::HeaderValue::from_str(s).ok())
+    {
+        Some(value) => {
+            headers.insert(TRACESTATE_HEADER, value);
+        }
+        None => {
+            headers.remove(TRACESTATE_HEADER);
+        }
+    }
+}
+
+#[cfg(feature = "http")]
+const TRACEPARENT_HEADER: &str = "traceparent";
+#[cfg(feature = "http")]
+const TRACESTATE_HEADER: &str = "tracestate";
+
+pub(crate) fn format_trace_id(trace_id: u128) -> String {
+    format!("{:032x}", trace_id)
+}
+
+/// The spec only allows lowercase hex
+fn parse_lower_hex(value: &str) -> Option<&str> {
+    if value
+        .bytes()
+        .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+    {
+        Some(value)
+    } else {
+        None
+    }
+}
+
+/// Generates a new random, non-zero, 128-bit trace id
+///
+/// This uses the randomly seeded hasher from the standard library, which is plenty for
+/// uniqueness but shouldn't be relied upon for anything cryptographic
+pub(crate) fn generate_trace_id() -> u128 {
+    loop {
+        let id = (u128::from(random_u64()) << 64) | u128::from(random_u64());
+        if id != 0 {
+            return id;
+        }
+    }
+}
+
+/// Generates a random, non-zero, 64-bit span id. Every instance of a service needs to send
+/// different span ids in its `traceparent`, so they can't be derived from the tracing `Id` or a
+/// counter, see [`generate_trace_id`]
+pub(crate) fn generate_span_id() -> u64 {
+    loop {
+        let id = random_u64();
+        if id != 0 {
+            return id;
+        }
+    }
+}
+
+fn random_u64() -> u64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(RANDOM_COUNTER.fetch_add(1, Ordering::Relaxed));
+    if let Ok(now) = SystemTime::now().duration_since(UNIX_EPOCH) {
+        hasher.write_u128(now.as_nanos());
+    }
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+    const SPAN_ID: &str = "00f067aa0ba902b7";
+
+    fn traceparent(version: &str, trace_id: &str, span_id: &str, flags: &str) -> String {
+        format!("{}-{}-{}-{}", version, trace_id, span_id, flags)
+    }
+
+    #[test]
+    fn parse_traceparent() {
+        let context =
+            TraceContext::parse_traceparent(&traceparent("00", TRACE_ID, SPAN_ID, "01")).unwrap();
+        assert_eq!(context.trace_id_hex(), TRACE_ID);
+        assert_eq!(context.span_id, 0x00f0_67aa_0ba9_02b7);
+        assert_eq!(context.trace_flags, FLAG_SAMPLED);
+        assert_eq!(
+            context.traceparent(),
+            traceparent("00", TRACE_ID, SPAN_ID, "01")
+        );
+        // Future versions can append fields
+        let future = format!("{}-extra", traceparent("cc", TRACE_ID, SPAN_ID, "01"));
+        assert!(TraceContext::parse_traceparent(&future).is_some());
+    }
+
+    #[test]
+    fn parse_invalid_traceparent() {
+        let zeroes = "0".repeat(32);
+        let invalid = [
+            traceparent("zz", TRACE_ID, SPAN_ID, "01"),
+            traceparent("ff", TRACE_ID, SPAN_ID, "01"),
+            traceparent("0", TRACE_ID, SPAN_ID, "01"),
+            format!("{}-extra", traceparent("00", TRACE_ID, SPAN_ID, "01")),
+            traceparent("00", &TRACE_ID.to_uppercase(), SPAN_ID, "01"),
+            traceparent("00", &zeroes, SPAN_ID, "01"),
+            traceparent("00", TRACE_ID, &zeroes[..16], "01"),
+            traceparent("00", TRACE_ID, SPAN_ID, "1"),
+            traceparent("00", TRACE_ID, SPAN_ID, "0g"),
+            format!("00-{}-{}", TRACE_ID, SPAN_ID),
+        ];
+        for value in invalid.iter() {
+            assert!(
+                TraceContext::parse_traceparent(value).is_none(),
+                "{}",
+                value
+            );
+        }
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn extract_joins_every_tracestate_header() {
+        let mut headers = http::HeaderMap::new();
+        let value = traceparent("00", TRACE_ID, SPAN_ID, "01");
+        headers.insert(TRACEPARENT_HEADER, value.parse().unwrap());
+        headers.append(TRACESTATE_HEADER, "congo=t61rcWkgMzE".parse().unwrap());
+        headers.append(TRACESTATE_HEADER, "rojo=00f067aa0ba902b7".parse().unwrap());
+
+        let context = extract(&headers).unwrap();
+        assert_eq!(context.trace_id_hex(), TRACE_ID);
+        assert_eq!(
+            context.trace_state.as_deref(),
+            Some("congo=t61rcWkgMzE,rojo=00f067aa0ba902b7")
+        );
+
+        headers.remove(TRACESTATE_HEADER);
+        assert_eq!(extract(&headers).unwrap().trace_state, None);
+        headers.insert(TRACEPARENT_HEADER, "garbage".parse().unwrap());
+        assert!(extract(&headers).is_none());
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn inject_context_replaces_the_headers() {
+        let mut headers = http::HeaderMap::new();
+        headers.insert(TRACESTATE_HEADER, "stale=1".parse().unwrap());
+        let context = TraceContext::parse_traceparent(&traceparent("00", TRACE_ID, SPAN_ID, "01"))
+            .unwrap()
+            .with_trace_state(Some("congo=t61rcWkgMzE".to_string()));
+
+        inject_context(&context, &mut headers);
+        assert_eq!(
+            headers[TRACEPARENT_HEADER],
+            traceparent("00", TRACE_ID, SPAN_ID, "01").as_str()
+        );
+        assert_eq!(headers[TRACESTATE_HEADER], "congo=t61rcWkgMzE");
+
+        // Without any state the header is removed rather than left over from before
+        inject_context(&context.with_trace_state(None), &mut headers);
+        assert!(headers.get(TRACESTATE_HEADER).is_none());
+        assert_eq!(headers.get_all(TRACEPARENT_HEADER).iter().count(), 1);
+    }
+}
@@ -0,0 +1,115 @@
+use json::JsonValue;
+
+use crate::util::{flag_ops, Type};
+
+/// Adds fields to records as they're written, for values that change from one record to the next
+/// such as the current task id, a request scoped value from a task local or the memory in use.
+///
+/// Providers are called on the thread that's writing the record, after the attributes of the span
+/// or event have been gathered, so any field they add takes precedence over those.
+///
+/// Any closure that takes the fields of the record is a provider that applies to every record.
+///
+/// # Examples
+///
+/// ```
+/// use tracing_sprout::{FieldProvider, JsonValue, RecordTypes};
+///
+/// struct MemoryInUse;
+///
+/// impl FieldProvider for MemoryInUse {
+///     fn provide(&self, fields: &mut JsonValue) {
+///         let _ = fields.insert("memory_in_use", 1024);
+///     }
+///
+///     fn record_types(&self) -> RecordTypes {
+///         RecordTypes::CLOSE
+///     }
+/// }
+/// ```
+pub trait FieldProvider: Send + Sync {
+    /// Adds any fields to the record that's about to be written
+    fn provide(&self, fields: &mut JsonValue);
+
+    /// The records this provider applies to
+    ///
+    /// Defaults to [`RecordTypes::ALL`]
+    fn record_types(&self) -> RecordTypes {
+        RecordTypes::ALL
+    }
+}
+
+impl<F> FieldProvider for F
+where
+    F: Fn(&mut JsonValue) + Send + Sync,
+{
+    fn provide(&self, fields: &mut JsonValue) {
+        self(fields)
+    }
+}
+
+/// The types of record a [`FieldProvider`] applies to, the values can be combined with `|`, for
+/// example `RecordTypes::EVENT | RecordTypes::CLOSE`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct RecordTypes(u8);
+
+impl RecordTypes {
+    /// Events
+    pub const EVENT: RecordTypes = RecordTypes(1);
+    /// The `new` records written when a span is created
+    pub const NEW: RecordTypes = RecordTypes(1 << 1);
+    /// The `enter` & `exit` records written as a span is entered and exited
+    pub const ACTIVE: RecordTypes = RecordTypes(1 << 2);
+    /// The `record` records written when values are recorded on a span
+    pub const RECORD: RecordTypes = RecordTypes(1 << 3);
+    /// The `close` & `slow` records written when a span closes
+    pub const CLOSE: RecordTypes = RecordTypes(1 << 4);
+    /// The canonical log line written when a root span closes
+    pub const CANONICAL: RecordTypes = RecordTypes(1 << 5);
+    /// Every record, apart from the `summary` records of the span statistics which don't belong
+    /// to any span
+    pub const ALL: RecordTypes = RecordTypes(
+        Self::EVENT.0
+            | Self::NEW.0
+            | Self::ACTIVE.0
+            | Self::RECORD.0
+            | Self::CLOSE.0
+            | Self::CANONICAL.0,
+    );
+}
+
+impl From<Type> for RecordTypes {
+    fn from(span_type: Type) -> Self {
+        match span_type {
+            Type::Event => Self::EVENT,
+            Type::New => Self::NEW,
+            Type::Enter | Type::Exit => Self::ACTIVE,
+            Type::Record => Self::RECORD,
+            Type::Close | Type::Slow => Self::CLOSE,
+            Type::Canonical => Self::CANONICAL,
+            Type::Summary => Self(0),
+        }
+    }
+}
+
+flag_ops!(RecordTypes);
+
+/// The providers registered on the layer, along with the records each of them applies to
+#[derive(Default)]
+pub(crate) struct Providers(Vec<(RecordTypes, Box<dyn FieldProvider>)>);
+
+impl Providers {
+    pub(crate) fn push(&mut self, record_types: RecordTypes, provider: Box<dyn FieldProvider>) {
+        self.0.push((record_types, provider));
+    }
+
+    /// Runs every provider that applies to the type of record
+    pub(crate) fn provide(&self, span_type: Type, fields: &mut JsonValue) {
+        let record_type = RecordTypes::from(span_type);
+        for (record_types, provider) in &self.0 {
+            if record_types.contains(record_type) {
+                provider.provide(fields);
+            }
+        }
+    }
+}
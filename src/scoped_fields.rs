@@ -0,0 +1,94 @@
+use json::JsonValue;
+
+use std::cell::RefCell;
+use std::marker::PhantomData;
+
+thread_local! {
+    /// The fields pushed on this thread, in the order they were pushed
+    static FIELDS: RefCell<Vec<(String, JsonValue)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Runs the closure with the fields added to every event emitted on this thread until it returns,
+/// much like a mapped diagnostic context _(MDC)_.
+///
+/// This is meant for code paths that don't have spans, such as sync libraries or thread pools.
+/// The fields are merged into events alongside the attributes of the span they're in _(if there
+/// is one)_, the event's own fields win if there's a collision, as do fields pushed later on.
+///
+/// # Examples
+///
+/// ```
+/// use tracing::info;
+///
+/// tracing_sprout::with_fields([("job_id", 42)], || {
+///     info!("This event has the job id");
+/// });
+/// ```
+pub fn with_fields<I, K, V, F, R>(fields: I, f: F) -> R
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: Into<String>,
+    V: Into<JsonValue>,
+    F: FnOnce() -> R,
+{
+    let _guard = push_fields(fields);
+    f()
+}
+
+/// Adds the fields to every event emitted on this thread until the returned guard is dropped,
+/// see [`with_fields`]
+///
+/// # Examples
+///
+/// ```
+/// use tracing::info;
+///
+/// let _guard = tracing_sprout::push_fields([("worker", "pool-1")]);
+/// info!("This event has the worker");
+/// ```
+pub fn push_fields<I, K, V>(fields: I) -> FieldsGuard
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: Into<String>,
+    V: Into<JsonValue>,
+{
+    FIELDS.with(|stack| {
+        let mut stack = stack.borrow_mut();
+        let len = stack.len();
+        stack.extend(
+            fields
+                .into_iter()
+                .map(|(key, value)| (key.into(), value.into())),
+        );
+        FieldsGuard {
+            len,
+            _not_send: PhantomData,
+        }
+    })
+}
+
+/// Removes the fields that were pushed with [`push_fields`] when it's dropped.
+///
+/// The fields belong to the thread they were pushed on, so the guard can't be sent to another.
+#[must_use = "the fields are removed as soon as the guard is dropped"]
+#[derive(Debug)]
+pub struct FieldsGuard {
+    len: usize,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Drop for FieldsGuard {
+    fn drop(&mut self) {
+        // If the thread local has already been destroyed there is nothing left to remove
+        let _ = FIELDS.try_with(|stack| stack.borrow_mut().truncate(self.len));
+    }
+}
+
+/// Runs the closure against every field pushed on this thread, oldest first
+pub(crate) fn for_each_field(mut f: impl FnMut(&str, &JsonValue)) {
+    let _ = FIELDS.try_with(|stack| {
+        for (key, value) in stack.borrow().iter() {
+            f(key, value);
+        }
+    });
+}
@@ -0,0 +1,114 @@
+use tracing_core::metadata::{Level, Metadata};
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Thresholds for flagging spans that took too long.
+///
+/// When a span closes, its lifetime is compared against the most specific threshold that
+/// applies to it, first by span name, then by target _(the longest matching prefix wins, like
+/// an `EnvFilter` directive)_ and finally the default. If it was too slow its `close` record is
+/// marked with `slow: true` and raised to [`Level::WARN`] _(or the level of your choosing)_ even if
+/// the span was created at a lower level.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use tracing_sprout::SlowSpans;
+///
+/// let slow_spans = SlowSpans::new()
+///     .with_default(Duration::from_millis(500))
+///     .with_target("my_app::db", Duration::from_millis(50))
+///     .with_span("login_handler", Duration::from_millis(200));
+/// ```
+#[derive(Debug, Clone)]
+pub struct SlowSpans {
+    default: Option<Duration>,
+    spans: HashMap<String, Duration>,
+    targets: Vec<(String, Duration)>,
+    level: Level,
+    separate_record: bool,
+}
+
+impl Default for SlowSpans {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SlowSpans {
+    /// Creates an empty set of thresholds, on its own this won't flag any spans as slow
+    pub fn new() -> Self {
+        Self {
+            default: None,
+            spans: HashMap::new(),
+            targets: Vec::new(),
+            level: Level::WARN,
+            separate_record: false,
+        }
+    }
+
+    /// The threshold used for any span that doesn't have a more specific one
+    pub fn with_default(mut self, threshold: Duration) -> Self {
+        self.default = Some(threshold);
+        self
+    }
+
+    /// The threshold for every span with the given name
+    pub fn with_span(mut self, name: impl Into<String>, threshold: Duration) -> Self {
+        self.spans.insert(name.into(), threshold);
+        self
+    }
+
+    /// The threshold for every span whose target starts with the given prefix
+    pub fn with_target(mut self, target: impl Into<String>, threshold: Duration) -> Self {
+        self.targets.push((target.into(), threshold));
+        self
+    }
+
+    /// The level that slow spans are raised to, a span that's already at a more severe level is
+    /// left as it is
+    ///
+    /// Defaults to [`Level::WARN`]
+    pub fn with_level(mut self, level: Level) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Also write a separate `slow` record for each slow span. This is written even if `close`
+    /// records have been turned off with [`SpanEvents`](crate::SpanEvents), so you can cut down
+    /// on the volume without losing sight of the slow spans.
+    ///
+    /// Defaults to `false`
+    pub fn with_separate_record(mut self, separate_record: bool) -> Self {
+        self.separate_record = separate_record;
+        self
+    }
+
+    pub(crate) fn level(&self) -> Level {
+        self.level
+    }
+
+    pub(crate) fn separate_record(&self) -> bool {
+        self.separate_record
+    }
+
+    /// Returns the threshold the span exceeded, if it was slow
+    pub(crate) fn exceeded(&self, metadata: &Metadata, elapsed: Duration) -> Option<Duration> {
+        self.threshold(metadata)
+            .filter(|threshold| elapsed > *threshold)
+    }
+
+    fn threshold(&self, metadata: &Metadata) -> Option<Duration> {
+        if let Some(threshold) = self.spans.get(metadata.name()) {
+            return Some(*threshold);
+        }
+        self.targets
+            .iter()
+            .filter(|(target, _)| metadata.target().starts_with(target.as_str()))
+            .max_by_key(|(target, _)| target.len())
+            .map(|(_, threshold)| *threshold)
+            .or(self.default)
+    }
+}
@@ -0,0 +1,43 @@
+use crate::util::flag_ops;
+
+/// Which points in a span's lifecycle produce a record, this mirrors `FmtSpan` from
+/// tracing-subscriber.
+///
+/// The values can be combined with `|`, for example `SpanEvents::NEW | SpanEvents::CLOSE` _(which
+/// is the default)_.
+///
+/// Spans backing async code are typically entered and exited every time the future is polled, so
+/// [`SpanEvents::ENTER`] & [`SpanEvents::EXIT`] can produce a lot of records. If the volume is a
+/// concern [`SpanEvents::CLOSE`] on its own still gives you the timings of each span, or
+/// [`SpanEvents::NONE`] leaves only the events.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct SpanEvents(u8);
+
+impl SpanEvents {
+    /// A record is written when the span is created
+    pub const NEW: SpanEvents = SpanEvents(1);
+    /// A record is written every time the span is entered
+    pub const ENTER: SpanEvents = SpanEvents(1 << 1);
+    /// A record is written every time the span is exited
+    pub const EXIT: SpanEvents = SpanEvents(1 << 2);
+    /// A record is written when the span is closed
+    pub const CLOSE: SpanEvents = SpanEvents(1 << 3);
+    /// A record is written every time values are recorded on the span after it was created,
+    /// holding only the newly recorded fields
+    pub const RECORD: SpanEvents = SpanEvents(1 << 4);
+    /// No records are written for the span lifecycle, only for events
+    pub const NONE: SpanEvents = SpanEvents(0);
+    /// A record is written every time the span is entered or exited
+    pub const ACTIVE: SpanEvents = SpanEvents(Self::ENTER.0 | Self::EXIT.0);
+    /// A record is written at every point in the span lifecycle
+    pub const FULL: SpanEvents =
+        SpanEvents(Self::NEW.0 | Self::ACTIVE.0 | Self::CLOSE.0 | Self::RECORD.0);
+}
+
+impl Default for SpanEvents {
+    fn default() -> Self {
+        Self::NEW | Self::CLOSE
+    }
+}
+
+flag_ops!(SpanEvents);
@@ -0,0 +1,431 @@
+use crate::duration::DurationUnit;
+use json::JsonValue;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Each power of two is split into `2^SUB_BUCKET_BITS` buckets, giving a relative error of ~9%
+const SUB_BUCKET_BITS: u32 = 3;
+const SUB_BUCKETS: u32 = 1 << SUB_BUCKET_BITS;
+const BUCKETS: usize = 64 * SUB_BUCKETS as usize;
+
+/// What the span durations are grouped by
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StatsKey {
+    /// The name of the span, i.e. `db_query`
+    Name,
+    /// The names of the span and all of its ancestors, i.e. `http_request::db_query`
+    Path,
+}
+
+/// Aggregates the duration of every span that closes into per span latency histograms.
+///
+/// This gives you rough latency metrics from your existing instrumentation without having to run
+/// a metrics backend. A `summary` record per span is written every `interval` and when the layer
+/// is dropped, and the numbers can be read at any time with [`SpanStatsHandle::snapshot`].
+///
+/// The statistics are cumulative from when the layer was created, until
+/// [`SpanStatsHandle::reset`] is called.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use tracing_sprout::{SpanStats, StatsKey, TrunkLayer};
+///
+/// let stats = SpanStats::new()
+///     .with_interval(Duration::from_secs(60))
+///     .with_key(StatsKey::Path);
+/// let handle = stats.handle();
+///
+/// let layer = TrunkLayer::new("I am Groot".to_string(), "0.1.0".to_string(), std::io::stdout)
+///     .with_span_stats(stats);
+///
+/// for stat in handle.snapshot() {
+///     println!("{} took {:?} at the 99th percentile", stat.key, stat.p99);
+/// }
+/// ```
+#[derive(Debug)]
+pub struct SpanStats {
+    interval: Option<Duration>,
+    key: StatsKey,
+    shared: Arc<Shared>,
+}
+
+impl Default for SpanStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SpanStats {
+    /// Creates an aggregator keyed by span name that only writes a summary when dropped
+    pub fn new() -> Self {
+        Self {
+            interval: None,
+            key: StatsKey::Name,
+            shared: Arc::new(Shared::default()),
+        }
+    }
+
+    /// How often a summary is written, this is checked whenever a span closes so a quiet
+    /// application may write them less often
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = Some(interval);
+        self
+    }
+
+    /// Choose what the durations are grouped by
+    ///
+    /// Defaults to [`StatsKey::Name`]
+    pub fn with_key(mut self, key: StatsKey) -> Self {
+        self.key = key;
+        self
+    }
+
+    /// A handle to read the statistics from, this can be taken before handing the aggregator to
+    /// the layer
+    pub fn handle(&self) -> SpanStatsHandle {
+        SpanStatsHandle {
+            shared: self.shared.clone(),
+        }
+    }
+
+    pub(crate) fn key(&self) -> StatsKey {
+        self.key
+    }
+
+    pub(crate) fn set_sink(&self, sink: Sink) {
+        if let Ok(mut s) = self.shared.sink.lock() {
+            *s = Some(sink);
+        }
+    }
+
+    /// The summary is written in the same unit as the rest of the records
+    pub(crate) fn set_duration_unit(&self, duration_unit: DurationUnit) {
+        if let Ok(mut unit) = self.shared.duration_unit.lock() {
+            *unit = duration_unit;
+        }
+    }
+
+    /// The summary has the same static fields as the rest of the records
+    pub(crate) fn set_static_fields(&self, static_fields: JsonValue) {
+        if let Ok(mut fields) = self.shared.static_fields.lock() {
+            *fields = Some(static_fields);
+        }
+    }
+
+    pub(crate) fn record(&self, key: String, duration: Duration) {
+        let due = match self.shared.state.lock() {
+            Ok(mut state) => {
+                state
+                    .histograms
+                    .entry(key)
+                    .or_insert_with(Histogram::new)
+                    .record(duration);
+                match self.interval {
+                    Some(interval) if state.last_summary.elapsed() >= interval => {
+                        state.last_summary = Instant::now();
+                        true
+                    }
+                    _ => false,
+                }
+            }
+            Err(_) => false,
+        };
+        if due {
+            self.shared.write_summary();
+        }
+    }
+}
+
+impl Drop for SpanStats {
+    fn drop(&mut self) {
+        self.shared.write_summary();
+    }
+}
+
+/// A cheap to clone handle for reading the statistics gathered by [`SpanStats`]
+#[derive(Debug, Clone)]
+pub struct SpanStatsHandle {
+    shared: Arc<Shared>,
+}
+
+impl SpanStatsHandle {
+    /// The statistics for every span that has closed so far, ordered by key
+    pub fn snapshot(&self) -> Vec<SpanStatsSnapshot> {
+        self.shared.snapshot()
+    }
+
+    /// Writes a summary record for every span right now, useful when shutting down as a global
+    /// subscriber is never dropped
+    pub fn flush(&self) {
+        self.shared.write_summary();
+    }
+
+    /// Clears all of the statistics gathered so far
+    pub fn reset(&self) {
+        if let Ok(mut state) = self.shared.state.lock() {
+            state.histograms.clear();
+        }
+    }
+}
+
+/// The statistics of a single span _(or span path)_
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpanStatsSnapshot {
+    /// The span name or path, depending on the [`StatsKey`]
+    pub key: String,
+    /// How many times the span has closed
+    pub count: u64,
+    pub min: Duration,
+    pub max: Duration,
+    pub mean: Duration,
+    /// The percentiles are approximate, to within ~9%
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+}
+
+pub(crate) type Sink = Box<dyn Fn(&[SpanStatsSnapshot], DurationUnit, &JsonValue) + Send + Sync>;
+
+#[derive(Default)]
+struct Shared {
+    state: Mutex<State>,
+    /// Where the summary records are written, this is provided by the layer
+    sink: Mutex<Option<Sink>>,
+    duration_unit: Mutex<DurationUnit>,
+    /// The fields every summary record starts from, this is provided by the layer
+    static_fields: Mutex<Option<JsonValue>>,
+}
+
+impl fmt::Debug for Shared {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Shared")
+            .field("state", &self.state)
+            .finish()
+    }
+}
+
+impl Shared {
+    fn snapshot(&self) -> Vec<SpanStatsSnapshot> {
+        let state = match self.state.lock() {
+            Ok(state) => state,
+            Err(_) => return Vec::new(),
+        };
+        let mut snapshot: Vec<_> = state
+            .histograms
+            .iter()
+            .map(|(key, histogram)| histogram.snapshot(key))
+            .collect();
+        snapshot.sort_by(|a, b| a.key.cmp(&b.key));
+        snapshot
+    }
+
+    fn write_summary(&self) {
+        let snapshot = self.snapshot();
+        if snapshot.is_empty() {
+            return;
+        }
+        let duration_unit = self
+            .duration_unit
+            .lock()
+            .map(|unit| *unit)
+            .unwrap_or_default();
+        let static_fields = self
+            .static_fields
+            .lock()
+            .ok()
+            .and_then(|fields| fields.clone())
+            .unwrap_or_else(JsonValue::new_object);
+        if let Ok(sink) = self.sink.lock() {
+            if let Some(sink) = sink.as_ref() {
+                sink(&snapshot, duration_unit, &static_fields);
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+struct State {
+    histograms: HashMap<String, Histogram>,
+    last_summary: Instant,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            histograms: HashMap::new(),
+            last_summary: Instant::now(),
+        }
+    }
+}
+
+/// A log-linear histogram of durations in nanoseconds
+#[derive(Debug)]
+struct Histogram {
+    buckets: Vec<u64>,
+    count: u64,
+    sum: u128,
+    min: u64,
+    max: u64,
+}
+
+impl Histogram {
+    fn new() -> Self {
+        Self {
+            buckets: vec![0; BUCKETS],
+            count: 0,
+            sum: 0,
+            min: u64::MAX,
+            max: 0,
+        }
+    }
+
+    fn record(&mut self, duration: Duration) {
+        let nanos = duration.as_nanos().min(u128::from(u64::MAX)) as u64;
+        self.buckets[bucket_of(nanos)] += 1;
+        self.count += 1;
+        self.sum += u128::from(nanos);
+        self.min = self.min.min(nanos);
+        self.max = self.max.max(nanos);
+    }
+
+    /// The upper bound of the bucket holding the quantile, capped by the largest value seen
+    fn quantile(&self, quantile: f64) -> u64 {
+        let rank = ((self.count as f64) * quantile).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (bucket, count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return upper_bound_of(bucket).min(self.max);
+            }
+        }
+        self.max
+    }
+
+    fn snapshot(&self, key: &str) -> SpanStatsSnapshot {
+        let mean = if self.count == 0 {
+            0
+        } else {
+            (self.sum / u128::from(self.count)) as u64
+        };
+        SpanStatsSnapshot {
+            key: key.to_string(),
+            count: self.count,
+            min: Duration::from_nanos(if self.count == 0 { 0 } else { self.min }),
+            max: Duration::from_nanos(self.max),
+            mean: Duration::from_nanos(mean),
+            p50: Duration::from_nanos(self.quantile(0.5)),
+            p90: Duration::from_nanos(self.quantile(0.9)),
+            p99: Duration::from_nanos(self.quantile(0.99)),
+        }
+    }
+}
+
+/// Values below `SUB_BUCKETS` get a bucket each, after that every power of two is split evenly
+fn bucket_of(nanos: u64) -> usize {
+    if nanos < u64::from(SUB_BUCKETS) {
+        return nanos as usize;
+    }
+    let exponent = 63 - nanos.leading_zeros();
+    // The bits just below the most significant one pick the sub bucket
+    let shift = exponent - SUB_BUCKET_BITS;
+    let sub_bucket = (nanos >> shift) as u32 & (SUB_BUCKETS - 1);
+    ((shift + 1) * SUB_BUCKETS + sub_bucket) as usize
+}
+
+fn upper_bound_of(bucket: usize) -> u64 {
+    let bucket = bucket as u32;
+    if bucket < SUB_BUCKETS {
+        return u64::from(bucket);
+    }
+    let shift = bucket / SUB_BUCKETS - 1;
+    let sub_bucket = u64::from(bucket % SUB_BUCKETS);
+    let lower = (1u64 << (shift + SUB_BUCKET_BITS)) | (sub_bucket << shift);
+    lower.saturating_add((1u64 << shift) - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_values_have_their_own_bucket() {
+        for nanos in 0..16 {
+            assert_eq!(bucket_of(nanos), nanos as usize);
+            assert_eq!(upper_bound_of(nanos as usize), nanos);
+        }
+    }
+
+    #[test]
+    fn bucket_boundaries() {
+        // From 16 each bucket is 2 wide, up until 32 where they become 4 wide
+        assert_eq!(bucket_of(16), 16);
+        assert_eq!(bucket_of(17), 16);
+        assert_eq!(bucket_of(18), 17);
+        assert_eq!(upper_bound_of(16), 17);
+        assert_eq!(bucket_of(31), 23);
+        assert_eq!(bucket_of(32), 24);
+        assert_eq!(upper_bound_of(24), 35);
+        assert!(bucket_of(u64::MAX) < BUCKETS);
+        assert_eq!(upper_bound_of(bucket_of(u64::MAX)), u64::MAX);
+    }
+
+    #[test]
+    fn every_value_is_within_its_bucket() {
+        let values = (0..64)
+            .flat_map(|exponent| {
+                let power = 1u64 << exponent;
+                vec![power - 1, power, power + 1, power + power / 3]
+            })
+            .chain(vec![999, 1_000_000, 123_456_789, u64::MAX]);
+        for nanos in values {
+            let bucket = bucket_of(nanos);
+            assert!(nanos <= upper_bound_of(bucket), "{}", nanos);
+            if bucket > 0 {
+                assert!(nanos > upper_bound_of(bucket - 1), "{}", nanos);
+            }
+            // The upper bound is never more than 1/8th over
+            assert!(upper_bound_of(bucket) - nanos <= nanos / u64::from(SUB_BUCKETS));
+        }
+    }
+
+    #[test]
+    fn quantiles() {
+        let mut histogram = Histogram::new();
+        for millis in 1..=100 {
+            histogram.record(Duration::from_millis(millis));
+        }
+        let millis = |quantile| histogram.quantile(quantile) as f64 / 1_000_000.0;
+        for (quantile, expected) in [(0.5, 50.0), (0.9, 90.0), (0.99, 99.0)].iter() {
+            let actual = millis(*quantile);
+            assert!(
+                actual >= *expected && actual <= expected * 1.125,
+                "p{} was {}ms",
+                quantile * 100.0,
+                actual
+            );
+        }
+        // Capped by the largest value rather than the top of its bucket
+        assert_eq!(histogram.quantile(1.0), 100_000_000);
+
+        let snapshot = histogram.snapshot("request");
+        assert_eq!(snapshot.count, 100);
+        assert_eq!(snapshot.min, Duration::from_millis(1));
+        assert_eq!(snapshot.max, Duration::from_millis(100));
+        assert_eq!(snapshot.mean, Duration::from_micros(50_500));
+    }
+
+    #[test]
+    fn quantiles_of_a_single_value() {
+        let mut histogram = Histogram::new();
+        histogram.record(Duration::from_micros(1234));
+        for quantile in [0.0, 0.5, 0.9, 0.99, 1.0].iter() {
+            assert_eq!(histogram.quantile(*quantile), 1_234_000);
+        }
+        assert_eq!(Histogram::new().quantile(0.5), 0);
+    }
+}
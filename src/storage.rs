@@ -1,81 +1,320 @@
 use json::{object, JsonValue};
 use tracing::field::{Field, Visit};
+use tracing::{Id, Level};
 
+use tracing_subscriber::registry::{LookupSpan, SpanRef};
+use tracing_subscriber::Registry;
+
+use std::collections::{HashMap, HashSet};
 use std::fmt;
-use std::time::Instant;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use crate::Result;
+use crate::inheritance::Inheritance;
+use crate::propagation::FLAG_SAMPLED;
 
 #[derive(Debug)]
 pub struct SproutStorage {
+    /// The merged attributes, i.e. the inherited attributes plus the ones recorded on this span
     pub(crate) attributes: JsonValue,
-    pub(crate) entered_at: Option<Instant>,
+    /// Only the attributes that were recorded directly on this span
+    pub(crate) fields: JsonValue,
+    /// The policies for inheriting the attributes, shared by every span in the trace
+    pub(crate) inheritance: Option<Arc<Inheritance>>,
+    /// How many levels each recorded attribute is from the span it was recorded on, this is only
+    /// tracked when there are inheritance policies
+    pub(crate) depths: HashMap<String, usize>,
+    /// The fields removed from this span since it was created, so that they stay removed when the
+    /// attributes are rebuilt from the ancestors
+    pub(crate) removed: HashSet<String>,
+    pub(crate) timings: Timings,
+    /// When the root span of this span's trace was created, shared by every descendant
+    pub(crate) root_created_at: Option<Instant>,
+    /// The id of the span this storage belongs to, in the format configured on the layer
+    pub(crate) span_id: Option<u64>,
+    pub(crate) parent_span_id: Option<u64>,
+    pub(crate) root_span_id: Option<u64>,
+    /// The W3C trace context, shared by every span in the trace
+    pub(crate) trace_id: Option<u128>,
+    pub(crate) trace_flags: u8,
+    pub(crate) trace_state: Option<String>,
+    /// The events that happened within this span, including those within its descendants
+    pub(crate) event_counts: EventCounts,
+    /// Whether an error was recorded within this span
+    pub(crate) errored: bool,
+    /// Only present on root spans when writing canonical log lines, every field recorded within
+    /// the root span is merged into this
+    pub(crate) canonical: Option<JsonValue>,
+    /// The records buffered for this span when writing whole trees as one document
+    pub(crate) tree: TreeNode,
 }
 
+/// Cloning the storage gives the storage of a child, which only inherits the attributes
 impl Clone for SproutStorage {
     fn clone(&self) -> Self {
+        let mut attributes = self.attributes.clone();
+        let mut depths = self.depths.clone();
+        if let Some(inheritance) = &self.inheritance {
+            inheritance.descend(&mut attributes, &mut depths);
+        }
         Self {
-            attributes: self.attributes.clone(),
-            entered_at: None,
+            attributes,
+            fields: JsonValue::new_object(),
+            inheritance: self.inheritance.clone(),
+            depths,
+            removed: HashSet::new(),
+            timings: Timings::new(),
+            root_created_at: self.root_created_at,
+            span_id: self.span_id,
+            parent_span_id: self.parent_span_id,
+            root_span_id: self.root_span_id,
+            trace_id: self.trace_id,
+            trace_flags: self.trace_flags,
+            trace_state: self.trace_state.clone(),
+            event_counts: EventCounts::default(),
+            errored: false,
+            canonical: None,
+            tree: TreeNode::default(),
         }
     }
 }
 
 impl SproutStorage {
-    pub fn new(name: &str, version: &str) -> Self {
-        let attributes = object! {
-            "name": name,
-            "version": version
-        };
+    /// Creates the storage of a root span, seeded with the given attributes
+    pub fn new(attributes: JsonValue) -> Self {
         Self {
             attributes,
-            entered_at: None,
+            fields: JsonValue::new_object(),
+            inheritance: None,
+            depths: HashMap::new(),
+            removed: HashSet::new(),
+            timings: Timings::new(),
+            root_created_at: None,
+            span_id: None,
+            parent_span_id: None,
+            root_span_id: None,
+            trace_id: None,
+            trace_flags: FLAG_SAMPLED,
+            trace_state: None,
+            event_counts: EventCounts::default(),
+            errored: false,
+            canonical: None,
+            tree: TreeNode::default(),
         }
     }
 
+    /// Makes this storage belong to a new span, with the span that it was cloned from (if any)
+    /// becoming the parent
+    pub(crate) fn assign_span_id(&mut self, id: u64) {
+        self.parent_span_id = self.span_id.take();
+        self.root_span_id = self.root_span_id.or(Some(id));
+        self.span_id = Some(id);
+    }
+
     pub fn clone_attributes(&self) -> JsonValue {
         self.attributes.clone()
     }
 
-    pub fn add_attribute_opt<T>(&mut self, key: &str, value: Option<T>) -> Result<()>
+    /// Removes a field from both the merged attributes and this span's own fields, returning the
+    /// merged value if there was one
+    pub(crate) fn remove_field(&mut self, key: &str) -> Option<JsonValue> {
+        self.fields.remove(key);
+        self.depths.remove(key);
+        self.removed.insert(key.to_string());
+        Some(self.attributes.remove(key)).filter(|value| !value.is_null())
+    }
+
+    /// Records a field against both the merged attributes and this span's own fields
+    pub(crate) fn record_field<T>(&mut self, key: &str, value: T)
     where
         T: Into<JsonValue>,
     {
-        if let Some(v) = value {
-            self.attributes.insert(key, v)?;
+        let value = value.into();
+        self.removed.remove(key);
+        self.fields
+            .insert(key, value.clone())
+            .expect("Root should always be a json object");
+        match &self.inheritance {
+            Some(inheritance) => {
+                inheritance.insert(&mut self.attributes, &mut self.depths, key, value)
+            }
+            None => self.attributes.insert(key, value),
         }
-        Ok(())
+        .expect("Root should always be a json object");
     }
 }
 
+/// The records of a span that are held back until its root span closes
+#[derive(Debug, Default)]
+pub(crate) struct TreeNode {
+    /// The events & lifecycle records of the span, in the order they happened
+    pub(crate) events: Vec<JsonValue>,
+    /// The child spans, in the order they closed
+    pub(crate) children: Vec<JsonValue>,
+}
+
+/// Counts of the events that happened within a span, by level
+#[derive(Debug, Default, Clone)]
+pub(crate) struct EventCounts {
+    pub(crate) trace: u64,
+    pub(crate) debug: u64,
+    pub(crate) info: u64,
+    pub(crate) warn: u64,
+    pub(crate) error: u64,
+    /// The message of the first `ERROR` event
+    pub(crate) first_error: Option<String>,
+}
+
+impl EventCounts {
+    pub(crate) fn record(&mut self, level: &Level, message: &str) {
+        match *level {
+            Level::TRACE => self.trace += 1,
+            Level::DEBUG => self.debug += 1,
+            Level::INFO => self.info += 1,
+            Level::WARN => self.warn += 1,
+            Level::ERROR => {
+                self.error += 1;
+                if self.first_error.is_none() {
+                    self.first_error = Some(message.to_string());
+                }
+            }
+        }
+    }
+
+    pub(crate) fn to_json(&self) -> JsonValue {
+        object! {
+            "trace": self.trace,
+            "debug": self.debug,
+            "info": self.info,
+            "warn": self.warn,
+            "error": self.error,
+        }
+    }
+}
+
+/// Tracks how a span has spent its life.
+///
+/// A span backing a future is entered and exited every time the future is polled, so the time
+/// spent actually doing work _(busy)_ and waiting to be polled again _(idle)_ are summed across
+/// every enter/exit pair.
+#[derive(Debug)]
+pub(crate) struct Timings {
+    pub(crate) created_at: Instant,
+    /// When the span was most recently entered
+    pub(crate) entered_at: Option<Instant>,
+    pub(crate) enter_count: u64,
+    busy: Duration,
+    idle: Duration,
+    /// When the span last went from idle to busy or vice versa
+    last_transition: Instant,
+    /// A span can be entered on more than one thread at a time, so only the outermost
+    /// enter/exit are transitions
+    active: usize,
+}
+
+impl Timings {
+    pub(crate) fn new() -> Self {
+        let now = Instant::now();
+        Self {
+            created_at: now,
+            entered_at: None,
+            enter_count: 0,
+            busy: Duration::default(),
+            idle: Duration::default(),
+            last_transition: now,
+            active: 0,
+        }
+    }
+
+    pub(crate) fn enter(&mut self) {
+        let now = Instant::now();
+        if self.active == 0 {
+            self.idle += now - self.last_transition;
+            self.last_transition = now;
+        }
+        self.active += 1;
+        self.enter_count += 1;
+        self.entered_at = Some(now);
+    }
+
+    pub(crate) fn exit(&mut self) {
+        let now = Instant::now();
+        self.active = self.active.saturating_sub(1);
+        if self.active == 0 {
+            self.busy += now - self.last_transition;
+            self.last_transition = now;
+        }
+    }
+
+    /// The total time the span has spent busy, including the current enter if it is entered
+    pub(crate) fn busy(&self) -> Duration {
+        if self.active > 0 {
+            self.busy + self.last_transition.elapsed()
+        } else {
+            self.busy
+        }
+    }
+
+    /// The total time the span has spent idle, including now if it isn't entered
+    pub(crate) fn idle(&self) -> Duration {
+        if self.active > 0 {
+            self.idle
+        } else {
+            self.idle + self.last_transition.elapsed()
+        }
+    }
+
+    /// The time since the span was created
+    pub(crate) fn lifetime(&self) -> Duration {
+        self.created_at.elapsed()
+    }
+}
+
+/// Runs the closure against a span of the default subscriber, or the current span if no id is
+/// given.
+///
+/// This is only possible when the default subscriber is built on top of a
+/// `tracing_subscriber::Registry`, otherwise there is nowhere to look the span up.
+pub(crate) fn with_span<T>(
+    id: Option<&Id>,
+    f: impl FnOnce(SpanRef<'_, Registry>) -> Option<T>,
+) -> Option<T> {
+    // `get_default` takes an `FnMut`, so the closure has to be moved out of an option
+    let mut f = Some(f);
+    tracing::dispatcher::get_default(|dispatch| {
+        let current = dispatch.current_span();
+        let id = id.or_else(|| current.id())?;
+        let registry = dispatch.downcast_ref::<Registry>()?;
+        let span = registry.span(id)?;
+        f.take().and_then(|f| f(span))
+    })
+}
+
+/// Runs the closure against the storage of the current span, see [`with_span`]
+pub(crate) fn with_current_storage<T>(f: impl FnOnce(&SproutStorage) -> T) -> Option<T> {
+    with_span(None, |span| span.extensions().get::<SproutStorage>().map(f))
+}
+
 /// Taken verbatim from tracing-subscriber
 impl Visit for SproutStorage {
     /// Visit a signed 64-bit integer value.
     fn record_i64(&mut self, field: &Field, value: i64) {
-        self.attributes
-            .insert(field.name(), value)
-            .expect("Root should always be a json object");
+        self.record_field(field.name(), value);
     }
 
     /// Visit an unsigned 64-bit integer value.
     fn record_u64(&mut self, field: &Field, value: u64) {
-        self.attributes
-            .insert(field.name(), value)
-            .expect("Root should always be a json object");
+        self.record_field(field.name(), value);
     }
 
     /// Visit a boolean value.
     fn record_bool(&mut self, field: &Field, value: bool) {
-        self.attributes
-            .insert(field.name(), value)
-            .expect("Root should always be a json object");
+        self.record_field(field.name(), value);
     }
 
     /// Visit a string value.
     fn record_str(&mut self, field: &Field, value: &str) {
-        self.attributes
-            .insert(field.name(), value)
-            .expect("Root should always be a json object");
+        self.record_field(field.name(), value);
     }
 
     fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
@@ -83,9 +322,7 @@ impl Visit for SproutStorage {
             // Skip fields that are actually log metadata that have already been handled
             name if name.starts_with("log.") => (),
             name if name.starts_with("r#") => {
-                self.attributes
-                    .insert(&name[2..], format!("{:?}", value).as_str())
-                    .expect("Root should always be a json object");
+                self.record_field(&name[2..], format!("{:?}", value).as_str());
             }
             name => {
                 let val = format!("{:?}", value);
@@ -99,9 +336,7 @@ impl Visit for SproutStorage {
                 } else {
                     val.into()
                 };
-                self.attributes
-                    .insert(name, val)
-                    .expect("Root should always be a json object");
+                self.record_field(name, val);
             }
         };
     }
@@ -9,25 +9,51 @@ use crate::Result;
 
 #[derive(Debug, Copy, Clone)]
 pub(crate) enum Type {
+    /// The span was created
+    New,
+    /// The span was entered
     Enter,
     Event,
+    /// The span was exited, it may well be entered again
     Exit,
+    /// Values were recorded on the span after it was created
+    Record,
+    /// The span was closed, it won't be entered again
+    Close,
+    /// The span took longer than its configured threshold
+    Slow,
+    /// The aggregated statistics of a span
+    Summary,
+    /// Everything that happened within a root span
+    Canonical,
 }
 
 impl Type {
     pub(crate) fn as_str(&self) -> &str {
         match *self {
+            Self::New => "new",
             Self::Enter => "enter",
             Self::Event => "event",
             Self::Exit => "exit",
+            Self::Record => "record",
+            Self::Close => "close",
+            Self::Slow => "slow",
+            Self::Summary => "summary",
+            Self::Canonical => "canonical",
         }
     }
 
     pub(crate) fn as_msg(&self) -> &str {
         match *self {
-            Self::Enter => "START",
+            Self::New => "START",
+            Self::Enter => "ENTER",
             Self::Event => "EVENT",
-            Self::Exit => "END",
+            Self::Exit => "EXIT",
+            Self::Record => "RECORD",
+            Self::Close => "END",
+            Self::Slow => "SLOW",
+            Self::Summary => "SUMMARY",
+            Self::Canonical => "CANONICAL",
         }
     }
 }
@@ -42,13 +68,19 @@ pub(crate) fn level_as_str(level: &Level) -> &str {
     }
 }
 
+/// Adds a field to a record. It would be nice for it to have this value, but if it fails, it fails
+/// _(which only happens when the record isn't an object)_
+pub(crate) fn insert_lossy(obj: &mut JsonValue, key: &str, value: impl Into<JsonValue>) {
+    obj.insert(key, value).ok();
+}
+
 pub(crate) fn insert_core_fields(
     obj: &mut JsonValue,
     metadata: &Metadata,
+    level: &Level,
     msg: &str,
 ) -> Result<()> {
     let now = Local::now();
-    let level = metadata.level();
 
     obj.insert(TIME, now.to_rfc2822())?;
     obj.insert(MESSAGE, msg)?;
@@ -63,21 +95,64 @@ pub(crate) fn insert_core_fields(
     Ok(())
 }
 
+/// Serializes the record, the level is usually the one from the metadata but it can be raised
+/// _(i.e. for a slow span)_
 pub(crate) fn serialize_span(
-    mut attributes: JsonValue,
+    attributes: JsonValue,
     metadata: &Metadata,
+    level: &Level,
     span_type: Type,
 ) -> Result<Vec<u8>> {
+    let record = build_span(attributes, metadata, level, span_type)?;
+
+    // I'd like to shrink this down, but it seems better to be safe than sorry?
+    let mut buffer = Vec::with_capacity(1024);
+    record.write(&mut buffer)?;
+
+    Ok(buffer)
+}
+
+/// Adds the core fields to the record without serializing it, so that it can be nested within
+/// another
+pub(crate) fn build_span(
+    mut attributes: JsonValue,
+    metadata: &Metadata,
+    level: &Level,
+    span_type: Type,
+) -> Result<JsonValue> {
     let msg = match span_type {
         Type::Event => format_event_message(metadata, &attributes),
-        Type::Enter | Type::Exit => format_span_context(metadata, span_type),
+        Type::New
+        | Type::Enter
+        | Type::Exit
+        | Type::Record
+        | Type::Close
+        | Type::Slow
+        | Type::Summary
+        | Type::Canonical => format_span_context(metadata, span_type),
     };
-    insert_core_fields(&mut attributes, metadata, &msg)?;
+    insert_core_fields(&mut attributes, metadata, level, &msg)?;
     attributes.insert(TYPE, span_type.as_str())?;
     // We remove the message, because we have added in our custom `msg` property
     attributes.remove("message");
 
-    // I'd like to shrink this down, but it seems better to be safe than sorry?
+    Ok(attributes)
+}
+
+/// Serializes a record that doesn't belong to any span or event, such as a summary. These are
+/// always written at `INFO` under the `sprout` target
+pub(crate) fn serialize_standalone(
+    mut attributes: JsonValue,
+    name: &str,
+    span_type: Type,
+) -> Result<Vec<u8>> {
+    let msg = format!("[{} | {}]", name.to_uppercase(), span_type.as_msg());
+    attributes.insert(TIME, Local::now().to_rfc2822())?;
+    attributes.insert(MESSAGE, msg)?;
+    attributes.insert(LEVEL, level_as_str(&Level::INFO))?;
+    attributes.insert(TARGET, SPROUT_TARGET)?;
+    attributes.insert(TYPE, span_type.as_str())?;
+
     let mut buffer = Vec::with_capacity(1024);
     attributes.write(&mut buffer)?;
 
@@ -89,7 +164,14 @@ pub(crate) fn serialize_span(
 /// `[LOGIN_HANDLER | START]`
 pub(crate) fn format_span_context(metadata: &Metadata, span_type: Type) -> String {
     match span_type {
-        Type::Enter | Type::Exit => format!(
+        Type::New
+        | Type::Enter
+        | Type::Exit
+        | Type::Record
+        | Type::Close
+        | Type::Slow
+        | Type::Summary
+        | Type::Canonical => format!(
             "[{} | {}]",
             metadata.name().to_uppercase(),
             span_type.as_msg()
@@ -110,9 +192,44 @@ pub(crate) fn format_event_message(metadata: &Metadata, attributes: &JsonValue)
     };
 
     // If the event is in the context of a span, prepend the span name to the message.
-    return format!(
+    format!(
         "{} {}",
         &format_span_context(metadata, Type::Event),
         message
-    );
+    )
+}
+
+/// Implements `|`, `|=` & `&` for a set of flags wrapping an integer, along with `contains` which
+/// is never true for an empty set
+macro_rules! flag_ops {
+    ($flags:ident) => {
+        impl $flags {
+            pub(crate) fn contains(&self, other: $flags) -> bool {
+                (*self & other) == other && other.0 != 0
+            }
+        }
+
+        impl std::ops::BitOr for $flags {
+            type Output = Self;
+
+            fn bitor(self, rhs: Self) -> Self::Output {
+                Self(self.0 | rhs.0)
+            }
+        }
+
+        impl std::ops::BitOrAssign for $flags {
+            fn bitor_assign(&mut self, rhs: Self) {
+                self.0 |= rhs.0;
+            }
+        }
+
+        impl std::ops::BitAnd for $flags {
+            type Output = Self;
+
+            fn bitand(self, rhs: Self) -> Self::Output {
+                Self(self.0 & rhs.0)
+            }
+        }
+    };
 }
+pub(crate) use flag_ops;
@@ -0,0 +1,76 @@
+mod common;
+
+use tracing::{info, info_span};
+use tracing_sprout::{ErrorPropagation, JsonValue, SpanEvents, TrunkLayer};
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::Registry;
+
+use common::Capture;
+
+/// Every record written by `f`, with only the canonical log line and event records turned on
+fn records<F: FnOnce()>(event_records: bool, f: F) -> Vec<JsonValue> {
+    let capture = Capture::default();
+    let layer = TrunkLayer::new("test".to_string(), "0.0.0".to_string(), capture.clone())
+        .with_canonical_log_line(true)
+        .with_span_events(SpanEvents::NONE)
+        .with_event_records(event_records)
+        .with_error_propagation(ErrorPropagation::Span);
+    let subscriber = Registry::default().with(layer);
+    tracing::subscriber::with_default(subscriber, f);
+    capture.records()
+}
+
+#[test]
+fn one_line_per_request_without_event_records() {
+    let records = records(false, || {
+        let root = info_span!("request", route = "/groot");
+        let _root = root.enter();
+        let child = info_span!("query", table = "trees");
+        let _child = child.enter();
+        info!(rows = 3, "Queried");
+    });
+    assert_eq!(records.len(), 1);
+    let line = &records[0];
+    assert_eq!(line["span_type"], "canonical");
+    assert_eq!(line["level"], "info");
+    assert_eq!(line["route"], "/groot");
+    assert_eq!(line["table"], "trees");
+    assert_eq!(line["rows"], 3);
+}
+
+#[test]
+fn event_records_are_still_written_alongside_the_line() {
+    let records = records(true, || {
+        let root = info_span!("request");
+        let _root = root.enter();
+        info!("I am Groot");
+    });
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0]["span_type"], "event");
+    assert_eq!(records[1]["span_type"], "canonical");
+}
+
+#[test]
+fn a_root_level_error_raises_the_line_to_error() {
+    let records = records(false, || {
+        let root = info_span!("request", error = tracing::field::Empty);
+        let _root = root.enter();
+        root.record("error", "connection reset");
+    });
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0]["level"], "error");
+    assert_eq!(records[0]["status"], "error");
+}
+
+#[test]
+fn an_error_event_anywhere_raises_the_line_to_error() {
+    let records = records(false, || {
+        let root = info_span!("request");
+        let _root = root.enter();
+        let child = info_span!("query");
+        let _child = child.enter();
+        tracing::error!("Query failed");
+    });
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0]["level"], "error");
+}
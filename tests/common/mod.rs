@@ -0,0 +1,39 @@
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use tracing_sprout::JsonValue;
+use tracing_subscriber::fmt::MakeWriter;
+
+/// Collects everything the layer writes so the records can be inspected afterwards
+#[derive(Clone, Default)]
+pub struct Capture(Arc<Mutex<Vec<u8>>>);
+
+impl Capture {
+    pub fn records(&self) -> Vec<JsonValue> {
+        let bytes = self.0.lock().unwrap();
+        std::str::from_utf8(&bytes)
+            .unwrap()
+            .lines()
+            .map(|line| json::parse(line).unwrap())
+            .collect()
+    }
+}
+
+impl Write for Capture {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for Capture {
+    type Writer = Capture;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
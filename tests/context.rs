@@ -0,0 +1,81 @@
+mod common;
+
+use tracing::{info, info_span};
+use tracing_sprout::{Inherit, Inheritance, JsonValue, SpanEvents, SproutContext, TrunkLayer};
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::Registry;
+
+use common::Capture;
+
+fn layer(capture: &Capture) -> TrunkLayer<Capture> {
+    let inheritance = Inheritance::new()
+        .with_field("secret", Inherit::Never)
+        .with_field("request_body", Inherit::Levels(1));
+    TrunkLayer::new("test".to_string(), "0.0.0".to_string(), capture.clone())
+        .with_span_events(SpanEvents::NEW)
+        .with_inheritance(inheritance)
+}
+
+/// Captures the context within a span that recorded one field of each policy
+fn capture_context() -> SproutContext {
+    let capture = Capture::default();
+    let subscriber = Registry::default().with(layer(&capture));
+    tracing::subscriber::with_default(subscriber, || {
+        let span = info_span!(
+            "request",
+            request_id = 7,
+            secret = "hunter2",
+            request_body = "{}"
+        );
+        let _guard = span.enter();
+        SproutContext::capture()
+    })
+}
+
+/// The records written by a span created within the context, and an event within that span
+fn records_within(context: &SproutContext) -> (JsonValue, JsonValue) {
+    let capture = Capture::default();
+    let subscriber = Registry::default().with(layer(&capture));
+    tracing::subscriber::with_default(subscriber, || {
+        context.in_scope(|| {
+            let span = info_span!("job");
+            let _guard = span.enter();
+            info!("working");
+        })
+    });
+    let mut records = capture.records().into_iter();
+    (records.next().unwrap(), records.next().unwrap())
+}
+
+#[test]
+fn capture_applies_the_inheritance_policies() {
+    let context = capture_context();
+    assert_eq!(context.attributes()["request_id"], 7);
+    assert!(context.attributes()["secret"].is_null());
+    // The context is one level down, which is as far as the request body goes
+    assert_eq!(context.attributes()["request_body"], "{}");
+
+    let (span, event) = records_within(&context);
+    assert_eq!(span["request_id"], 7);
+    assert_eq!(span["request_body"], "{}");
+    assert!(span["secret"].is_null());
+    assert_eq!(event["request_id"], 7);
+    assert!(event["request_body"].is_null());
+}
+
+#[test]
+fn depths_are_passed_through_the_environment() {
+    let vars = capture_context().to_env();
+    for (key, value) in &vars {
+        std::env::set_var(key, value);
+    }
+    let context = SproutContext::from_env();
+    for (key, _) in &vars {
+        std::env::remove_var(key);
+    }
+
+    let (span, event) = records_within(&context);
+    assert_eq!(span["request_body"], "{}");
+    assert!(event["request_body"].is_null());
+    assert_eq!(event["request_id"], 7);
+}
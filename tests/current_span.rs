@@ -0,0 +1,41 @@
+mod common;
+
+use tracing::{info, info_span};
+use tracing_sprout::{current_span, SpanEvents, TrunkLayer};
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::Registry;
+
+use common::Capture;
+
+#[test]
+fn removed_fields_stay_removed_with_live_inheritance() {
+    let capture = Capture::default();
+    let layer = TrunkLayer::new("test".to_string(), "0.0.0".to_string(), capture.clone())
+        .with_span_events(SpanEvents::NONE)
+        .with_live_inheritance(true);
+    let subscriber = Registry::default().with(layer);
+
+    tracing::subscriber::with_default(subscriber, || {
+        let parent = info_span!("parent", tenant = "acme");
+        let _parent = parent.enter();
+        let child = info_span!("child");
+        let _child = child.enter();
+
+        assert_eq!(current_span::remove("tenant").unwrap(), "acme");
+        info!("removed");
+        {
+            let grandchild = info_span!("grandchild");
+            let _grandchild = grandchild.enter();
+            info!("removed from the grandchild too");
+        }
+
+        current_span::insert("tenant", "globex");
+        info!("recorded again");
+    });
+
+    let records = capture.records();
+    assert_eq!(records.len(), 3);
+    assert!(records[0]["tenant"].is_null());
+    assert!(records[1]["tenant"].is_null());
+    assert_eq!(records[2]["tenant"], "globex");
+}
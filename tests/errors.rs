@@ -0,0 +1,66 @@
+mod common;
+
+use tracing::{info, info_span};
+use tracing_sprout::{ErrorPropagation, JsonValue, SpanEvents, TrunkLayer};
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::Registry;
+
+use common::Capture;
+
+/// The `close` record of the only span created by `f`
+fn close_record<F: FnOnce()>(f: F) -> JsonValue {
+    let capture = Capture::default();
+    let layer = TrunkLayer::new("test".to_string(), "0.0.0".to_string(), capture.clone())
+        .with_span_events(SpanEvents::CLOSE)
+        .with_error_propagation(ErrorPropagation::Span);
+    let subscriber = Registry::default().with(layer);
+    tracing::subscriber::with_default(subscriber, f);
+
+    let mut records: Vec<_> = capture
+        .records()
+        .into_iter()
+        .filter(|record| record["span_type"] == "close")
+        .collect();
+    assert_eq!(records.len(), 1);
+    records.remove(0)
+}
+
+#[test]
+fn error_false_does_not_mark_the_span() {
+    let record = close_record(|| {
+        let span = info_span!("request", error = false);
+        let _guard = span.enter();
+    });
+    assert_eq!(record["level"], "info");
+    assert_eq!(record["error"], false);
+    assert!(record["status"].is_null());
+
+    let record = close_record(|| {
+        let span = info_span!("request", error = tracing::field::Empty);
+        let _guard = span.enter();
+        span.record("error", false);
+        info!(error = "", "Nothing went wrong");
+    });
+    assert_eq!(record["level"], "info");
+    assert!(record["status"].is_null());
+}
+
+#[test]
+fn error_true_or_a_message_marks_the_span() {
+    let record = close_record(|| {
+        let span = info_span!("request", error = tracing::field::Empty);
+        let _guard = span.enter();
+        span.record("error", true);
+    });
+    assert_eq!(record["level"], "error");
+    assert_eq!(record["error"], true);
+    assert_eq!(record["status"], "error");
+
+    let record = close_record(|| {
+        let span = info_span!("request");
+        let _guard = span.enter();
+        info!(error = "connection reset", "Request failed");
+    });
+    assert_eq!(record["level"], "error");
+    assert_eq!(record["status"], "error");
+}
@@ -0,0 +1,64 @@
+mod common;
+
+use tracing::{error, info, info_span, warn};
+use tracing_sprout::{JsonValue, SpanEvents, TrunkLayer};
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::Registry;
+
+use common::Capture;
+
+/// The `close` records written by `f`, in the order the spans closed
+fn close_records<F: FnOnce()>(f: F) -> Vec<JsonValue> {
+    let capture = Capture::default();
+    let layer = TrunkLayer::new("test".to_string(), "0.0.0".to_string(), capture.clone())
+        .with_span_events(SpanEvents::CLOSE)
+        .with_event_counts(true);
+    let subscriber = Registry::default().with(layer);
+    tracing::subscriber::with_default(subscriber, f);
+    capture
+        .records()
+        .into_iter()
+        .filter(|record| record["span_type"] == "close")
+        .collect()
+}
+
+#[test]
+fn descendant_events_are_counted() {
+    let records = close_records(|| {
+        let parent = info_span!("parent");
+        let _parent = parent.enter();
+        info!("Starting");
+        {
+            let child = info_span!("child");
+            let _child = child.enter();
+            warn!("Retrying");
+            error!("Connection reset");
+            error!("Gave up");
+        }
+        info!("Finished");
+    });
+
+    let child = &records[0];
+    assert_eq!(child["event_counts"]["info"], 0);
+    assert_eq!(child["event_counts"]["warn"], 1);
+    assert_eq!(child["event_counts"]["error"], 2);
+    assert_eq!(child["first_error"], "Connection reset");
+
+    let parent = &records[1];
+    assert_eq!(parent["event_counts"]["info"], 2);
+    assert_eq!(parent["event_counts"]["warn"], 1);
+    assert_eq!(parent["event_counts"]["error"], 2);
+    assert_eq!(parent["first_error"], "Connection reset");
+}
+
+#[test]
+fn first_error_is_left_out_without_errors() {
+    let records = close_records(|| {
+        let span = info_span!("request");
+        let _guard = span.enter();
+        info!("All good");
+    });
+    assert_eq!(records[0]["event_counts"]["info"], 1);
+    assert_eq!(records[0]["event_counts"]["error"], 0);
+    assert!(records[0]["first_error"].is_null());
+}
@@ -0,0 +1,44 @@
+mod common;
+
+use tracing::{info, info_span};
+use tracing_sprout::{SpanEvents, SpanIdFormat, TrunkLayer};
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::Registry;
+
+use common::Capture;
+
+#[test]
+fn an_explicit_parent_is_used_for_everything() {
+    let capture = Capture::default();
+    let layer = TrunkLayer::new("test".to_string(), "0.0.0".to_string(), capture.clone())
+        .with_span_events(SpanEvents::CLOSE)
+        .with_span_list(true)
+        .with_span_id_format(SpanIdFormat::Hex)
+        .with_event_counts(true);
+    let subscriber = Registry::default().with(layer);
+
+    tracing::subscriber::with_default(subscriber, || {
+        let b = info_span!("b", who = "b");
+        let a = info_span!("a", who = "a");
+        let _a = a.enter();
+        info!(parent: &b, "Sent to b");
+    });
+
+    let records = capture.records();
+    let event = records
+        .iter()
+        .find(|record| record["span_type"] == "event")
+        .unwrap();
+    let close = |name: &str| {
+        records
+            .iter()
+            .find(|record| record["span_type"] == "close" && record["who"] == name)
+            .unwrap()
+    };
+    assert_eq!(event["who"], "b");
+    assert_eq!(event["span_id"], close("b")["span_id"]);
+    assert_eq!(event["span_path"], "b");
+    assert_eq!(event["spans"][0]["id"], close("b")["span_id"]);
+    assert_eq!(close("b")["event_counts"]["info"], 1);
+    assert_eq!(close("a")["event_counts"]["info"], 0);
+}
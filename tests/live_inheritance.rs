@@ -0,0 +1,68 @@
+mod common;
+
+use tracing::{info, info_span};
+use tracing_sprout::{JsonValue, SpanEvents, TrunkLayer};
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::Registry;
+
+use common::Capture;
+
+/// The event records written when the parent records a value after the child was created
+fn late_parent_field(live_inheritance: bool) -> Vec<JsonValue> {
+    let capture = Capture::default();
+    let layer = TrunkLayer::new("test".to_string(), "0.0.0".to_string(), capture.clone())
+        .with_span_events(SpanEvents::NONE)
+        .with_live_inheritance(live_inheritance);
+    let subscriber = Registry::default().with(layer);
+
+    tracing::subscriber::with_default(subscriber, || {
+        let parent = info_span!("request", user = tracing::field::Empty);
+        let _parent = parent.enter();
+        let child = info_span!("handler");
+        let _child = child.enter();
+        info!("before authenticating");
+        parent.record("user", "alice");
+        info!("after authenticating");
+    });
+    capture.records()
+}
+
+#[test]
+fn parent_fields_recorded_later_reach_existing_children() {
+    let records = late_parent_field(true);
+    assert_eq!(records.len(), 2);
+    assert!(records[0]["user"].is_null());
+    assert_eq!(records[1]["user"], "alice");
+}
+
+#[test]
+fn children_keep_the_copy_from_when_they_were_created_by_default() {
+    let records = late_parent_field(false);
+    assert_eq!(records.len(), 2);
+    assert!(records[1]["user"].is_null());
+}
+
+#[test]
+fn record_diffs_start_from_the_live_value() {
+    let capture = Capture::default();
+    let layer = TrunkLayer::new("test".to_string(), "0.0.0".to_string(), capture.clone())
+        .with_span_events(SpanEvents::RECORD)
+        .with_record_diff(true)
+        .with_live_inheritance(true);
+    let subscriber = Registry::default().with(layer);
+
+    tracing::subscriber::with_default(subscriber, || {
+        let parent = info_span!("request", user = tracing::field::Empty);
+        let _parent = parent.enter();
+        let child = info_span!("handler", user = tracing::field::Empty);
+        let _child = child.enter();
+        parent.record("user", "alice");
+        child.record("user", "bob");
+    });
+
+    let records = capture.records();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[1]["user"], "bob");
+    assert_eq!(records[1]["changes"]["user"]["before"], "alice");
+    assert_eq!(records[1]["changes"]["user"]["after"], "bob");
+}
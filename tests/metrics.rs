@@ -0,0 +1,98 @@
+use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+use metrics_util::MetricKind;
+use tracing::{info, info_span, warn};
+use tracing_sprout::{SpanEvents, TrunkLayer};
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::Registry;
+
+type Labels = Vec<(String, String)>;
+
+/// Every metric recorded, as `(kind, name, sorted labels, value)`
+fn record<F: FnOnce()>(f: F) -> Vec<(MetricKind, String, Labels, DebugValue)> {
+    let recorder = DebuggingRecorder::new();
+    let snapshotter = recorder.snapshotter();
+
+    let layer = TrunkLayer::new("test".to_string(), "0.0.0".to_string(), std::io::sink)
+        .with_span_events(SpanEvents::NONE)
+        .with_metrics(["route", "method"]);
+    let subscriber = Registry::default().with(layer);
+
+    metrics::with_local_recorder(&recorder, || {
+        tracing::subscriber::with_default(subscriber, f)
+    });
+
+    snapshotter
+        .snapshot()
+        .into_vec()
+        .into_iter()
+        .map(|(key, _, _, value)| {
+            let (kind, key) = key.into_parts();
+            let mut labels: Vec<_> = key
+                .labels()
+                .map(|label| (label.key().to_string(), label.value().to_string()))
+                .collect();
+            labels.sort();
+            (kind, key.name().to_string(), labels, value)
+        })
+        .collect()
+}
+
+fn labels(pairs: &[(&str, &str)]) -> Labels {
+    pairs
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+#[test]
+fn span_duration_is_labelled_with_the_chosen_attributes() {
+    let metrics = record(|| {
+        // `method` is missing, so it's left off rather than recorded as null
+        let span = info_span!("http_request", route = "/groot", user_id = 42);
+        let _guard = span.enter();
+    });
+
+    let durations: Vec<_> = metrics
+        .iter()
+        .filter(|(kind, name, _, _)| *kind == MetricKind::Histogram && name == "span.duration")
+        .collect();
+    assert_eq!(durations.len(), 1);
+    let (_, _, span_labels, value) = durations[0];
+    assert_eq!(
+        span_labels,
+        &labels(&[("route", "/groot"), ("span", "http_request")])
+    );
+    match value {
+        DebugValue::Histogram(samples) => assert_eq!(samples.len(), 1),
+        other => panic!("expected a histogram, got {:?}", other),
+    }
+}
+
+#[test]
+fn events_are_counted_by_level_and_target() {
+    let metrics = record(|| {
+        info!("I am Groot");
+        info!("I am Groot");
+        warn!(target: "groot", "We are Groot");
+    });
+
+    let mut events: Vec<_> = metrics
+        .into_iter()
+        .filter(|(kind, name, _, _)| *kind == MetricKind::Counter && name == "events")
+        .map(|(_, _, labels, value)| (labels, value))
+        .collect();
+    events.sort_by(|a, b| a.0.cmp(&b.0));
+    assert_eq!(
+        events,
+        vec![
+            (
+                labels(&[("level", "info"), ("target", "metrics")]),
+                DebugValue::Counter(2)
+            ),
+            (
+                labels(&[("level", "warn"), ("target", "groot")]),
+                DebugValue::Counter(1)
+            ),
+        ]
+    );
+}
@@ -0,0 +1,71 @@
+mod common;
+
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_sdk::testing::trace::InMemorySpanExporterBuilder;
+use opentelemetry_sdk::trace::TracerProvider;
+use tracing::{info, info_span};
+use tracing_sprout::TrunkLayer;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::Registry;
+
+use common::Capture;
+
+#[test]
+fn ids_match_the_exported_spans() {
+    let exporter = InMemorySpanExporterBuilder::new().build();
+    let provider = TracerProvider::builder()
+        .with_simple_exporter(exporter.clone())
+        .build();
+    let capture = Capture::default();
+
+    let subscriber = Registry::default()
+        .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")))
+        .with(TrunkLayer::new(
+            "test".to_string(),
+            "0.0.0".to_string(),
+            capture.clone(),
+        ));
+
+    tracing::subscriber::with_default(subscriber, || {
+        let parent = info_span!("parent");
+        let _parent = parent.enter();
+        let child = info_span!("child");
+        let _child = child.enter();
+        info!("inside the child");
+    });
+
+    // The simple processor hands spans to the exporter on a background thread
+    provider.force_flush();
+    let exported = exporter.get_finished_spans().unwrap();
+    assert_eq!(exported.len(), 2);
+    let records = capture.records();
+    assert!(!records.is_empty());
+
+    for record in &records {
+        let span_id = record["span_id"].as_str().unwrap();
+        let span = exported
+            .iter()
+            .find(|span| span.span_context.span_id().to_string() == span_id)
+            .unwrap_or_else(|| panic!("no exported span with id {}", span_id));
+        let context = &span.span_context;
+        assert_eq!(record["trace_id"], context.trace_id().to_string().as_str());
+        assert_eq!(
+            record["trace_flags"],
+            format!("{:02x}", context.trace_flags().to_u8()).as_str()
+        );
+    }
+
+    let child = exported.iter().find(|span| span.name == "child").unwrap();
+    let event = records
+        .iter()
+        .find(|record| record["span_type"] == "event")
+        .unwrap();
+    assert_eq!(
+        event["span_id"],
+        child.span_context.span_id().to_string().as_str()
+    );
+    assert_eq!(
+        event["parent_span_id"],
+        child.parent_span_id.to_string().as_str()
+    );
+}
@@ -0,0 +1,34 @@
+mod common;
+
+use tracing::info_span;
+use tracing_sprout::{ProcessFields, SpanEvents, TrunkLayer};
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::Registry;
+
+use common::Capture;
+
+#[test]
+fn record_records_only_hold_the_recorded_fields() {
+    let capture = Capture::default();
+    let layer = TrunkLayer::new("test".to_string(), "0.0.0".to_string(), capture.clone())
+        .with_span_events(SpanEvents::RECORD)
+        .with_process_fields(ProcessFields::OS_THREAD_ID)
+        .with_record_diff(true);
+    let subscriber = Registry::default().with(layer);
+
+    tracing::subscriber::with_default(subscriber, || {
+        let span = info_span!("request", route = "/groot", user = tracing::field::Empty);
+        span.record("user", "alice");
+    });
+
+    let records = capture.records();
+    assert_eq!(records.len(), 1);
+    let record = &records[0];
+    assert_eq!(record["span_type"], "record");
+    assert_eq!(record["user"], "alice");
+    assert!(record["route"].is_null());
+    assert_eq!(record["name"], "test");
+    assert!(record["os_thread_id"].is_number(), "{}", record);
+    assert!(record["changes"]["user"]["before"].is_null());
+    assert_eq!(record["changes"]["user"]["after"], "alice");
+}
@@ -0,0 +1,97 @@
+mod common;
+
+use std::thread::sleep;
+use std::time::Duration;
+
+use tracing::{debug_span, error_span, info_span, Level};
+use tracing_sprout::{JsonValue, SlowSpans, SpanEvents, TrunkLayer};
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::Registry;
+
+use common::Capture;
+
+const HOUR: Duration = Duration::from_secs(60 * 60);
+
+fn slow_records<F: FnOnce()>(
+    span_events: SpanEvents,
+    slow_spans: SlowSpans,
+    f: F,
+) -> Vec<JsonValue> {
+    let capture = Capture::default();
+    let layer = TrunkLayer::new("test".to_string(), "0.0.0".to_string(), capture.clone())
+        .with_span_events(span_events)
+        .with_slow_spans(slow_spans);
+    let subscriber = Registry::default().with(layer);
+    tracing::subscriber::with_default(subscriber, f);
+    capture.records()
+}
+
+/// Enters the span for long enough to exceed a zero threshold
+fn run(span: tracing::Span) {
+    let _guard = span.enter();
+    sleep(Duration::from_millis(1));
+}
+
+#[test]
+fn the_most_specific_threshold_wins() {
+    let slow_spans = SlowSpans::new()
+        .with_default(Duration::ZERO)
+        .with_target("app", HOUR)
+        .with_target("app::db", Duration::ZERO)
+        .with_span("cached", HOUR);
+    let records = slow_records(SpanEvents::CLOSE, slow_spans, || {
+        run(info_span!(target: "other", "default"));
+        run(info_span!(target: "app::http", "target"));
+        run(info_span!(target: "app::db::pool", "longest_target"));
+        run(info_span!(target: "app::db", "cached"));
+    });
+
+    let slow: Vec<_> = records
+        .iter()
+        .filter(|record| record["slow"] == true)
+        .map(|record| record["msg"].as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(slow, vec!["[DEFAULT | END]", "[LONGEST_TARGET | END]"]);
+    for record in records.iter().filter(|record| record["slow"] == true) {
+        assert_eq!(record["slow_threshold_ms"], 0);
+    }
+}
+
+#[test]
+fn slow_spans_are_raised_to_the_configured_level() {
+    let records = slow_records(
+        SpanEvents::CLOSE,
+        SlowSpans::new().with_default(Duration::ZERO),
+        || {
+            run(debug_span!("debug"));
+            run(error_span!("error"));
+        },
+    );
+    assert_eq!(records[0]["level"], "warn");
+    // A span that's already more severe is left as it is
+    assert_eq!(records[1]["level"], "error");
+
+    let records = slow_records(
+        SpanEvents::CLOSE,
+        SlowSpans::new()
+            .with_default(Duration::ZERO)
+            .with_level(Level::ERROR),
+        || run(debug_span!("debug")),
+    );
+    assert_eq!(records[0]["level"], "error");
+}
+
+#[test]
+fn a_separate_record_is_written_without_close_records() {
+    let slow_spans = SlowSpans::new()
+        .with_span("slow", Duration::ZERO)
+        .with_separate_record(true);
+    let records = slow_records(SpanEvents::NONE, slow_spans, || {
+        run(info_span!("slow"));
+        run(info_span!("fast"));
+    });
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0]["span_type"], "slow");
+    assert_eq!(records[0]["slow"], true);
+    assert_eq!(records[0]["level"], "warn");
+}
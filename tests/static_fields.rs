@@ -0,0 +1,36 @@
+mod common;
+
+use tracing::info_span;
+use tracing_sprout::{SpanEvents, SpanStats, TrunkLayer};
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::Registry;
+
+use common::Capture;
+
+#[test]
+fn every_record_has_the_static_fields() {
+    let capture = Capture::default();
+    // Added after the stats, which are written by the aggregator rather than the layer
+    let layer = TrunkLayer::new("test".to_string(), "0.0.0".to_string(), capture.clone())
+        .with_span_events(SpanEvents::default() | SpanEvents::RECORD)
+        .with_span_stats(SpanStats::new())
+        .with_static_field("environment", "production");
+    let subscriber = Registry::default().with(layer);
+
+    tracing::subscriber::with_default(subscriber, || {
+        let span = info_span!("request", status = tracing::field::Empty);
+        let _guard = span.enter();
+        span.record("status", 200);
+    });
+
+    let records = capture.records();
+    for span_type in ["new", "record", "close", "summary"].iter() {
+        let record = records
+            .iter()
+            .find(|record| record["span_type"] == *span_type)
+            .unwrap_or_else(|| panic!("no {} record", span_type));
+        assert_eq!(record["name"], "test", "{}", span_type);
+        assert_eq!(record["version"], "0.0.0", "{}", span_type);
+        assert_eq!(record["environment"], "production", "{}", span_type);
+    }
+}
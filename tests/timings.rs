@@ -0,0 +1,48 @@
+mod common;
+
+use std::thread::sleep;
+use std::time::Duration;
+
+use tracing::info_span;
+use tracing_sprout::{SpanEvents, TrunkLayer};
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::Registry;
+
+use common::Capture;
+
+#[test]
+fn busy_and_idle_time_add_up_across_every_enter() {
+    let capture = Capture::default();
+    let layer = TrunkLayer::new("test".to_string(), "0.0.0".to_string(), capture.clone())
+        .with_span_events(SpanEvents::CLOSE);
+    let subscriber = Registry::default().with(layer);
+
+    tracing::subscriber::with_default(subscriber, || {
+        let span = info_span!("poll");
+        for _ in 0..3 {
+            let guard = span.enter();
+            sleep(Duration::from_millis(20));
+            drop(guard);
+            sleep(Duration::from_millis(10));
+        }
+    });
+
+    let records = capture.records();
+    assert_eq!(records.len(), 1);
+    let close = &records[0];
+    assert_eq!(close["enter_count"], 3);
+    let busy = close["busy_time_ms"].as_u64().unwrap();
+    let idle = close["idle_time_ms"].as_u64().unwrap();
+    let elapsed = close["elapsed_time_ms"].as_u64().unwrap();
+    assert!(busy >= 60, "busy for {}ms", busy);
+    assert!(idle >= 20, "idle for {}ms", idle);
+    // Each is rounded down on its own
+    assert!(
+        busy + idle <= elapsed + 1,
+        "{} + {} > {}",
+        busy,
+        idle,
+        elapsed
+    );
+    assert!(elapsed >= 90, "elapsed {}ms", elapsed);
+}
@@ -0,0 +1,90 @@
+mod common;
+
+use tracing::{info, info_span};
+use tracing_sprout::{JsonValue, SpanEvents, TrunkLayer};
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::Registry;
+
+use common::Capture;
+
+/// Every record written by `f`, with trace ids enabled
+fn records<F: FnOnce()>(f: F) -> Vec<JsonValue> {
+    let capture = Capture::default();
+    let layer = TrunkLayer::new("test".to_string(), "0.0.0".to_string(), capture.clone())
+        .with_span_events(SpanEvents::NEW)
+        .with_span_list(true)
+        .with_trace_ids(true);
+    let subscriber = Registry::default().with(layer);
+    tracing::subscriber::with_default(subscriber, f);
+    capture.records()
+}
+
+fn is_hex_span_id(value: &JsonValue) -> bool {
+    value
+        .as_str()
+        .is_some_and(|id| id.len() == 16 && id.bytes().all(|b| b.is_ascii_hexdigit()))
+}
+
+#[test]
+fn random_span_ids_are_written_as_hex() {
+    let records = records(|| {
+        let parent = info_span!("parent");
+        let _parent = parent.enter();
+        let child = info_span!("child");
+        let _child = child.enter();
+        info!("Within a trace");
+    });
+
+    let event = records.last().unwrap();
+    for key in ["span_id", "parent_span_id", "root_span_id"].iter() {
+        assert!(is_hex_span_id(&event[*key]), "{} was {}", key, event[*key]);
+    }
+    for span in event["spans"].members() {
+        assert!(is_hex_span_id(&span["id"]), "{}", span);
+    }
+}
+
+#[test]
+fn an_incoming_traceparent_is_adopted() {
+    let traceparent = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+    let mut current = None;
+    let records = records(|| {
+        let span = info_span!(
+            "http_request",
+            traceparent = traceparent,
+            tracestate = "congo=t61rcWkgMzE"
+        );
+        let _guard = span.enter();
+        current = tracing_sprout::TraceContext::current();
+        info!("Continuing the caller's trace");
+    });
+
+    for record in &records {
+        assert_eq!(record["trace_id"], "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(record["trace_flags"], "01");
+        // The caller's span is the remote parent of the root span
+        assert_eq!(record["parent_span_id"], "00f067aa0ba902b7");
+        assert!(record["traceparent"].is_null(), "{}", record);
+        assert!(record["tracestate"].is_null(), "{}", record);
+        assert!(record["spans"][0]["fields"]["traceparent"].is_null());
+    }
+    let current = current.unwrap();
+    assert_eq!(current.trace_state.as_deref(), Some("congo=t61rcWkgMzE"));
+    assert_eq!(
+        format!("{:016x}", current.span_id),
+        records[0]["span_id"].as_str().unwrap()
+    );
+}
+
+#[test]
+fn an_invalid_traceparent_starts_a_new_trace() {
+    let records = records(|| {
+        let span = info_span!(
+            "http_request",
+            traceparent = "zz-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+        );
+        let _guard = span.enter();
+    });
+    assert_ne!(records[0]["trace_id"], "4bf92f3577b34da6a3ce929d0e0e4736");
+    assert!(records[0]["parent_span_id"].is_null());
+}
@@ -0,0 +1,85 @@
+mod common;
+
+use tracing::{info, info_span};
+use tracing_sprout::{JsonValue, SpanEvents, TrunkLayer};
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::Registry;
+
+use common::Capture;
+
+/// Every record written by `f` when writing whole trees
+fn records<F: FnOnce()>(f: F) -> Vec<JsonValue> {
+    let capture = Capture::default();
+    let layer = TrunkLayer::new("test".to_string(), "0.0.0".to_string(), capture.clone())
+        .with_tree_documents(true)
+        .with_span_events(SpanEvents::NEW);
+    let subscriber = Registry::default().with(layer);
+    tracing::subscriber::with_default(subscriber, f);
+    capture.records()
+}
+
+/// The messages of the event records within a node
+fn messages(node: &JsonValue) -> Vec<String> {
+    node["events"]
+        .members()
+        .filter(|record| record["span_type"] == "event")
+        .map(|record| record["msg"].to_string())
+        .collect()
+}
+
+#[test]
+fn children_and_events_are_nested_under_their_span() {
+    let records = records(|| {
+        let root = info_span!("request");
+        let _root = root.enter();
+        info!("started");
+        {
+            let first = info_span!("first");
+            let _first = first.enter();
+            info!("in the first");
+            let grandchild = info_span!("grandchild");
+            let _grandchild = grandchild.enter();
+            info!("in the grandchild");
+        }
+        let second = info_span!("second");
+        let _second = second.enter();
+        info!("in the second");
+    });
+    assert_eq!(records.len(), 1);
+
+    let root = &records[0];
+    assert_eq!(root["span_type"], "close");
+    assert_eq!(root["msg"], "[REQUEST | END]");
+    assert_eq!(root["events"][0]["span_type"], "new");
+    assert_eq!(messages(root), ["[EVENT] started"]);
+    assert_eq!(root["children"].len(), 2);
+
+    // Children are in the order they closed
+    let first = &root["children"][0];
+    assert_eq!(first["msg"], "[FIRST | END]");
+    assert_eq!(messages(first), ["[EVENT] in the first"]);
+    assert_eq!(first["children"].len(), 1);
+    let grandchild = &first["children"][0];
+    assert_eq!(grandchild["msg"], "[GRANDCHILD | END]");
+    assert_eq!(messages(grandchild), ["[EVENT] in the grandchild"]);
+    assert!(grandchild["children"].is_empty());
+
+    let second = &root["children"][1];
+    assert_eq!(second["msg"], "[SECOND | END]");
+    assert_eq!(messages(second), ["[EVENT] in the second"]);
+}
+
+#[test]
+fn events_outside_of_a_span_are_written_straight_away() {
+    let records = records(|| {
+        info!("before");
+        let root = info_span!("request");
+        let _root = root.enter();
+        info!("within");
+    });
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0]["span_type"], "event");
+    assert_eq!(records[0]["msg"], "[EVENT] before");
+    assert_eq!(records[1]["msg"], "[REQUEST | END]");
+    assert_eq!(messages(&records[1]), ["[EVENT] within"]);
+}